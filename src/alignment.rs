@@ -1,6 +1,8 @@
 use bio_seq::prelude::{Dna, SeqSlice};
 use core::fmt::Debug;
 
+use crate::error::{Error, Result};
+
 pub trait QuasiAlignment: Clone {
     fn new(q_start: u32, q_end: u32, r_start: u32, r_end: u32, forward: bool) -> Self;
     fn q_start(&self) -> u32;
@@ -13,7 +15,9 @@ pub trait QuasiAlignment: Clone {
     fn set_r_start(&mut self, r_start: u32);
     fn set_r_end(&mut self, r_end: u32);
     fn set_forward(&mut self, forward: bool);
-    fn indel(&self) -> i32;
+    /// Difference between the reference and query spans; negative for
+    /// insertions in the query
+    fn indel(&self) -> Result<i32>;
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -76,15 +80,33 @@ impl QuasiAlignment for Alignment {
         self.forward = forward;
     }
 
-    fn indel(&self) -> i32 {
-        ((self.r_end - self.r_start) - (self.q_end - self.q_start))
-            .try_into()
-            .unwrap()
+    fn indel(&self) -> Result<i32> {
+        let span = |start: u32, end: u32| {
+            end.checked_sub(start)
+                .map(i64::from)
+                .ok_or(Error::InvalidCoordinates { start, end })
+        };
+        let indel = span(self.r_start, self.r_end)? - span(self.q_start, self.q_end)?;
+        // the difference of two u32 spans always fits in an i64, but not an i32
+        indel.try_into().map_err(|_| Error::IndelOverflow(indel))
     }
 }
 
 pub trait QuasiAlign<A: QuasiAlignment> {
     fn quasi_align(&self, seq: &SeqSlice<Dna>, gap: u32) -> Vec<A>;
+
+    /// Stream alignments as they are finalised, without materialising the
+    /// per-k-mer mapping of the query. The default collects `quasi_align`.
+    fn quasi_align_iter<'a>(
+        &'a self,
+        seq: &'a SeqSlice<Dna>,
+        gap: u32,
+    ) -> impl Iterator<Item = A> + 'a
+    where
+        A: 'a,
+    {
+        self.quasi_align(seq, gap).into_iter()
+    }
 }
 
 pub fn mergable<A: QuasiAlignment>(working: &A, current: &A) -> bool {
@@ -101,6 +123,22 @@ pub fn mergable<A: QuasiAlignment>(working: &A, current: &A) -> bool {
     }
 }
 
+/// Whether two segments lie on the same diagonal of the dot plot, so that
+/// merging them spans the positions between them without an indel
+pub fn diagonal<A: QuasiAlignment>(working: &A, current: &A) -> bool {
+    if working.forward() == current.forward() {
+        if working.forward() {
+            working.r_end() as i64 - working.q_end() as i64
+                == current.r_end() as i64 - current.q_end() as i64
+        } else {
+            working.r_start() as i64 + working.q_end() as i64
+                == current.r_start() as i64 + current.q_end() as i64
+        }
+    } else {
+        false
+    }
+}
+
 pub fn merge<A: QuasiAlignment>(working: &mut A, current: &A) {
     working.set_q_end(current.q_end());
     if working.forward() {
@@ -110,102 +148,181 @@ pub fn merge<A: QuasiAlignment>(working: &mut A, current: &A) {
     }
 }
 
-pub fn merge_segments<A: QuasiAlignment + Debug>(matches: Vec<Option<i32>>, k: u32) -> Vec<A> {
-    let mut alignments: Vec<A> = Vec::new();
-    let mut alignment: Option<A> = None; // the working alignment
-    let mut last_segment_end: u32 = 0;
-    //println!("{:?}", matches);
-
-    for (q_start, &mapping) in matches.iter().enumerate() {
-        let q_start: u32 = q_start as u32;
-        let q_end: u32 = q_start + k;
-
-        if q_start < last_segment_end {
-            //println!(
-            //    "\t\tskipping {}-{}, {:?} (last q_end: {})",
-            //    q_start, q_end, mapping, last_segment_end
-            //);
-            continue;
-        }
+pub fn merge_segments<A: QuasiAlignment + Debug>(
+    matches: Vec<Option<i32>>,
+    k: u32,
+) -> Result<Vec<A>> {
+    Segments::new(matches, k).collect()
+}
 
-        let segment: Option<A> = match mapping {
-            Some(0) => {
-                // if this kmer matches the reference ambiguously
-                //unimplemented!()
-                None
-            }
-            Some(r_pos) => {
-                // if this kmer matches the reference uniquely
-                let forward: bool = r_pos > 0;
+pub fn merge_contigs<A: QuasiAlignment + Debug>(unmerged: Vec<A>, gap_tolerance: u32) -> Vec<A> {
+    Contigs::new(unmerged, gap_tolerance).collect()
+}
 
-                let (r_start, r_end): (u32, u32) = if forward {
-                    let r_pos: u32 = r_pos as u32 - 1;
-                    (r_pos, r_pos + k)
-                } else {
-                    let r_pos: u32 = r_pos.unsigned_abs();
-                    (r_pos - k, r_pos)
-                };
-                Some(A::new(q_start, q_end, r_start, r_end, forward))
-            }
-            None => {
-                // if this kmer does not match the reference
-                None
-            }
-        };
+/// Lazily merge a stream of k-mer mappings into alignments. Each alignment
+/// is yielded as soon as it is closed by a mismatch or a discontinuity, so
+/// only the working alignment is held in memory.
+pub struct Segments<A: QuasiAlignment, I: Iterator<Item = Option<i32>>> {
+    matches: I,
+    k: u32,
+    stride: u32,
+    q_start: u32,
+    last_hit: u32,
+    last_segment_end: u32,
+    alignment: Option<A>, // the working alignment
+}
 
-        //println!("working alignment: {:?}", alignment);
-        //println!("new segment:       {:?}", segment);
+impl<A: QuasiAlignment, I: Iterator<Item = Option<i32>>> Segments<A, I> {
+    pub fn new<T: IntoIterator<IntoIter = I>>(matches: T, k: u32) -> Self {
+        Self::sampled(matches, k, 1)
+    }
 
-        match (&mut alignment, &segment) {
-            (None, None) => {
-                //println!("\t(no working alignment or segment)");
-            }
-            (Some(working_alignment), None) => {
-                // encountering a run of None, push alignment
-                //println!("\tworking alignment but segment None");
-                last_segment_end = working_alignment.q_end();
-                alignments.push(working_alignment.clone());
-                alignment = None;
+    /// Merge mappings against a reference where only every `stride`-th
+    /// k-mer was indexed. Up to `stride - 1` consecutive misses are expected
+    /// between hits and do not close the working alignment, and hits are
+    /// merged when they continue its diagonal.
+    pub fn sampled<T: IntoIterator<IntoIter = I>>(matches: T, k: u32, stride: u32) -> Self {
+        Segments {
+            matches: matches.into_iter(),
+            k,
+            stride: stride.max(1),
+            q_start: 0,
+            last_hit: 0,
+            last_segment_end: 0,
+            alignment: None,
+        }
+    }
+}
+
+impl<A: QuasiAlignment, I: Iterator<Item = Option<i32>>> Iterator for Segments<A, I> {
+    type Item = Result<A>;
+
+    /// A hit whose reference interval does not fit in `u32`, e.g. a reverse
+    /// hit ending fewer than `k` bases into the reference, is reported as
+    /// `InvalidCoordinates` and skipped without closing the working
+    /// alignment, so iteration can continue past it.
+    fn next(&mut self) -> Option<Result<A>> {
+        for mapping in self.matches.by_ref() {
+            let q_start: u32 = self.q_start;
+            self.q_start += 1;
+            let Some(q_end) = q_start.checked_add(self.k) else {
+                return Some(Err(Error::InvalidCoordinates {
+                    start: q_start,
+                    end: u32::MAX,
+                }));
+            };
+
+            if q_start < self.last_segment_end {
+                continue;
             }
-            (Some(working_alignment), Some(current_segment)) => {
-                // decide whether to merge current segment into working alignment
-                if mergable(working_alignment, current_segment) {
-                    //println!("\tmergable segment");
-                    merge(working_alignment, current_segment);
-                } else {
-                    //println!("\tnon-mergeable segment. new alignment");
-                    last_segment_end = working_alignment.q_end();
-                    alignments.push(working_alignment.clone());
-                    alignment = Some(current_segment.clone());
+
+            let segment: Option<A> = match mapping {
+                Some(0) => {
+                    // if this kmer matches the reference ambiguously
+                    None
+                }
+                Some(r_pos) => {
+                    // if this kmer matches the reference uniquely
+                    let forward: bool = r_pos > 0;
+
+                    let interval = if forward {
+                        let r_pos: u32 = r_pos as u32 - 1;
+                        r_pos.checked_add(self.k).map(|r_end| (r_pos, r_end)).ok_or(
+                            Error::InvalidCoordinates {
+                                start: r_pos,
+                                end: u32::MAX,
+                            },
+                        )
+                    } else {
+                        // a reverse hit is the k bases ending at its position
+                        let r_pos: u32 = r_pos.unsigned_abs();
+                        r_pos
+                            .checked_sub(self.k)
+                            .map(|r_start| (r_start, r_pos))
+                            .ok_or(Error::InvalidCoordinates {
+                                start: self.k,
+                                end: r_pos,
+                            })
+                    };
+                    let (r_start, r_end) = match interval {
+                        Ok(interval) => interval,
+                        Err(err) => return Some(Err(err)),
+                    };
+                    Some(A::new(q_start, q_end, r_start, r_end, forward))
+                }
+                None => {
+                    // if this kmer does not match the reference
+                    None
+                }
+            };
+
+            match (&mut self.alignment, segment) {
+                (None, None) => (),
+                (Some(_), None) if q_start - self.last_hit < self.stride => {
+                    // an unsampled reference position may still be on the diagonal
+                }
+                (Some(working_alignment), None) => {
+                    // encountering a run of None, close the working alignment
+                    self.last_segment_end = working_alignment.q_end();
+                    return self.alignment.take().map(Ok);
+                }
+                (Some(working_alignment), Some(current_segment)) => {
+                    // decide whether to merge current segment into working alignment
+                    let mergable = if self.stride > 1 {
+                        diagonal(working_alignment, &current_segment)
+                    } else {
+                        mergable(working_alignment, &current_segment)
+                    };
+                    self.last_hit = q_start;
+                    if mergable {
+                        merge(working_alignment, &current_segment);
+                    } else {
+                        self.last_segment_end = working_alignment.q_end();
+                        return self.alignment.replace(current_segment).map(Ok);
+                    }
+                }
+                (None, Some(current_segment)) => {
+                    // initialize new working alignment from this segment
+                    self.last_hit = q_start;
+                    self.alignment = Some(current_segment);
                 }
-            }
-            (None, Some(current_segment)) => {
-                // initialize new working alignment from this segment
-                //println!("\tNo working alignment, new segment");
-                alignment = Some(current_segment.clone());
             }
         }
+
+        // query exhausted, yield the remaining working alignment
+        self.alignment.take().map(Ok)
     }
+}
+
+/// Lazily chain alignments that are continuous within a gap tolerance on
+/// both the query and the reference.
+pub struct Contigs<A: QuasiAlignment, I: Iterator<Item = A>> {
+    alignments: I,
+    gap_tolerance: u32,
+    last: Option<A>,
+}
 
-    // if there is a remaining working alignment, push it
-    if let Some(remaining) = alignment {
-        //println!("\tquery exhausted, pushing remaining working segment");
-        alignments.push(remaining);
+impl<A: QuasiAlignment, I: Iterator<Item = A>> Contigs<A, I> {
+    pub fn new<T: IntoIterator<IntoIter = I>>(alignments: T, gap_tolerance: u32) -> Self {
+        Contigs {
+            alignments: alignments.into_iter(),
+            gap_tolerance,
+            last: None,
+        }
     }
-    alignments
 }
 
-pub fn merge_contigs<A: QuasiAlignment + Debug>(unmerged: Vec<A>, gap_tolerance: u32) -> Vec<A> {
-    // Hardcoded tolerance thresholds for merging
+impl<A: QuasiAlignment, I: Iterator<Item = A>> Iterator for Contigs<A, I> {
+    type Item = A;
 
-    let mut merged: Vec<A> = Vec::new();
+    fn next(&mut self) -> Option<A> {
+        let gap_tolerance = self.gap_tolerance as i32;
 
-    for alignment in unmerged {
-        if merged.is_empty() {
-            // If the merged list is empty, add the first alignment
-            merged.push(alignment);
-        } else {
-            let last_alignment = merged.last_mut().unwrap();
+        for alignment in self.alignments.by_ref() {
+            let Some(last_alignment) = &mut self.last else {
+                self.last = Some(alignment);
+                continue;
+            };
 
             // Check if the alignments are in the same direction
             if last_alignment.forward() == alignment.forward() {
@@ -213,40 +330,37 @@ pub fn merge_contigs<A: QuasiAlignment + Debug>(unmerged: Vec<A>, gap_tolerance:
                 // reversed contigs will be chained by a2.r_start -> a1.r_end
                 let valid_ref_gap: bool = if alignment.forward() {
                     (last_alignment.r_end() as i32 - alignment.r_start() as i32).abs()
-                        <= gap_tolerance as i32
+                        <= gap_tolerance
                 } else {
                     (last_alignment.r_start() as i32 - alignment.r_end() as i32).abs()
-                        <= gap_tolerance as i32
+                        <= gap_tolerance
                 };
 
                 // Check if the alignments are continuous within the tolerance
                 if (last_alignment.q_end() as i32 - alignment.q_start() as i32).abs()
-                    <= gap_tolerance as i32
+                    <= gap_tolerance
                     && valid_ref_gap
                 {
                     // Merge alignments
+                    last_alignment.set_q_end(alignment.q_end());
                     if alignment.forward() {
-                        last_alignment.set_q_end(alignment.q_end());
                         last_alignment.set_r_end(alignment.r_end());
                     } else {
-                        last_alignment.set_q_end(alignment.q_end());
                         last_alignment.set_r_start(alignment.r_start());
                     }
-                } else {
-                    // Append alignment
-                    merged.push(alignment);
+                    continue;
                 }
-            } else {
-                // Append alignment
-                merged.push(alignment);
             }
+
+            return self.last.replace(alignment);
         }
-    }
 
-    merged
+        self.last.take()
+    }
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
 
@@ -255,7 +369,7 @@ mod tests {
         // Testing an alignment scenario where the query is a substring of the reference
         let kmer_map = vec![Some(7), Some(8), Some(9), Some(10), Some(11)];
         let k = 5; // K-mer length is 5
-        let alignments: Vec<Alignment> = merge_segments(kmer_map, k);
+        let alignments: Vec<Alignment> = merge_segments(kmer_map, k).unwrap();
         assert_eq!(alignments.len(), 1);
         let a = &alignments[0];
         assert_eq!(
@@ -295,7 +409,7 @@ mod tests {
             Some(6),
         ];
         let k = 5; // K-mer length is 5
-        let alignments: Vec<Alignment> = merge_segments(kmer_map, k);
+        let alignments: Vec<Alignment> = merge_segments(kmer_map, k).unwrap();
         //println!("{:?}", alignments);
         //assert_eq!(alignments.len(), 2);
         let a = &alignments[0];
@@ -347,7 +461,7 @@ mod tests {
             Some(6),
         ];
         let k = 5; // K-mer length is 5
-        let alignments: Vec<Alignment> = merge_segments(kmer_map, k);
+        let alignments: Vec<Alignment> = merge_segments(kmer_map, k).unwrap();
         assert_eq!(alignments.len(), 2);
         let a = &alignments[0];
         assert_eq!(
@@ -379,7 +493,7 @@ mod tests {
 
         let kmer_map = vec![Some(1), Some(2), Some(3), Some(4), Some(5), Some(6)];
         let k = 5; // K-mer length is 5
-        let alignments: Vec<Alignment> = merge_segments(kmer_map, k);
+        let alignments: Vec<Alignment> = merge_segments(kmer_map, k).unwrap();
         assert_eq!(alignments.len(), 1);
 
         let a0 = &alignments[0];
@@ -401,7 +515,7 @@ mod tests {
 
         let kmer_map = vec![Some(-10), Some(-9), Some(-8), Some(-7), Some(-6), Some(-5)];
         let k = 5; // K-mer length is 5
-        let alignments: Vec<Alignment> = merge_segments(kmer_map, k);
+        let alignments: Vec<Alignment> = merge_segments(kmer_map, k).unwrap();
         assert_eq!(alignments.len(), 1);
 
         let a0 = &alignments[0];
@@ -426,7 +540,7 @@ mod tests {
 
         let kmer_map = vec![None, None, None, None, None];
         let k = 5; // K-mer length is 5
-        let alignments: Vec<Alignment> = merge_segments(kmer_map, k);
+        let alignments: Vec<Alignment> = merge_segments(kmer_map, k).unwrap();
         assert_eq!(alignments.len(), 0);
     }
 
@@ -441,7 +555,7 @@ mod tests {
 
         let kmer_map = vec![Some(1), None, None, None, Some(12), Some(13), Some(14)];
         let k = 5; // K-mer length is 5
-        let alignments: Vec<Alignment> = merge_segments(kmer_map, k);
+        let alignments: Vec<Alignment> = merge_segments(kmer_map, k).unwrap();
         assert_eq!(alignments.len(), 2);
 
         let a0 = &alignments[0];
@@ -493,7 +607,7 @@ mod tests {
             Some(15),
         ];
         let k = 5; // K-mer length is 5
-        let alignments: Vec<Alignment> = merge_segments(kmer_map, k);
+        let alignments: Vec<Alignment> = merge_segments(kmer_map, k).unwrap();
         println!("{:?}", alignments);
         assert_eq!(alignments.len(), 2);
         let a0 = &alignments[0];
@@ -550,7 +664,7 @@ mod tests {
             Some(-5),
         ];
         let k = 5; // K-mer length is 5
-        let alignments: Vec<Alignment> = merge_segments(kmer_map, k);
+        let alignments: Vec<Alignment> = merge_segments(kmer_map, k).unwrap();
         assert_eq!(alignments.len(), 4);
 
         let a0 = &alignments[0];
@@ -654,4 +768,145 @@ mod tests {
         assert_eq!(m.r_end(), 20);
         assert_eq!(m.forward(), false);
     }
+
+    #[test]
+    fn test_streaming_segments() {
+        // Segments yields each alignment as soon as it is closed
+        let kmer_map = vec![Some(1), Some(2), None, None, None, None, Some(-6), Some(-5)];
+        let k = 5;
+        let mut segments = Segments::<Alignment, _>::new(kmer_map.clone(), k);
+        assert_eq!(segments.next(), Some(Ok(Alignment::new(0, 6, 0, 6, true))));
+        assert_eq!(
+            segments.next(),
+            Some(Ok(Alignment::new(6, 12, 0, 6, false)))
+        );
+        assert_eq!(segments.next(), None);
+
+        let streamed: Vec<Alignment> =
+            Contigs::new(Segments::new(kmer_map.clone(), k).map(Result::unwrap), 1).collect();
+        let batched: Vec<Alignment> = merge_contigs(merge_segments(kmer_map, k).unwrap(), 1);
+        assert_eq!(streamed, batched);
+    }
+
+    #[test]
+    fn test_streaming_quasi_align() {
+        use crate::KmerMap;
+        use bio_seq::prelude::*;
+
+        let reference = dna!("ACGTGACGGTCGTACCACCAAAGT");
+        let query = dna!("ACGTGACGGTACGTGACGGT");
+        let map: KmerMap<5> = KmerMap::new(reference).unwrap();
+
+        let streamed: Vec<Alignment> = map.quasi_align_iter(query, 1).collect();
+        let batched: Vec<Alignment> = map.quasi_align(query, 1);
+        assert_eq!(streamed, batched);
+        assert_eq!(streamed[0], Alignment::new(0, 9, 0, 9, true));
+    }
+
+    #[test]
+    fn test_default_quasi_align_iter() {
+        use bio_seq::prelude::*;
+
+        // an implementor that only provides the batch method
+        struct Fixed;
+        impl QuasiAlign<Alignment> for Fixed {
+            fn quasi_align(&self, seq: &SeqSlice<Dna>, _gap: u32) -> Vec<Alignment> {
+                vec![Alignment::new(
+                    0,
+                    seq.len() as u32,
+                    0,
+                    seq.len() as u32,
+                    true,
+                )]
+            }
+        }
+
+        let streamed: Vec<Alignment> = Fixed.quasi_align_iter(dna!("ACGT"), 0).collect();
+        assert_eq!(streamed, vec![Alignment::new(0, 4, 0, 4, true)]);
+    }
+
+    #[test]
+    fn test_indel() {
+        assert_eq!(Alignment::new(0, 10, 5, 15, true).indel(), Ok(0));
+        assert_eq!(Alignment::new(0, 10, 5, 18, true).indel(), Ok(3));
+        // a query span longer than the reference span must not underflow
+        assert_eq!(Alignment::new(0, 10, 5, 8, true).indel(), Ok(-7));
+        assert_eq!(
+            Alignment::new(10, 0, 5, 8, true).indel(),
+            Err(Error::InvalidCoordinates { start: 10, end: 0 })
+        );
+        assert_eq!(
+            Alignment::new(0, 0, 0, u32::MAX, true).indel(),
+            Err(Error::IndelOverflow(u32::MAX as i64))
+        );
+    }
+
+    #[test]
+    fn test_segments_invalid_reverse_hit() {
+        // a reverse hit can not end fewer than k bases into the reference
+        let kmer_map = vec![Some(1), Some(2), Some(-3), Some(3)];
+        let mut segments = Segments::<Alignment, _>::new(kmer_map.clone(), 5);
+        assert_eq!(
+            segments.next(),
+            Some(Err(Error::InvalidCoordinates { start: 5, end: 3 }))
+        );
+        // the bad hit is skipped without closing the working alignment
+        assert_eq!(segments.next(), Some(Ok(Alignment::new(0, 8, 0, 7, true))));
+        assert_eq!(segments.next(), None);
+
+        assert!(merge_segments::<Alignment>(kmer_map, 5).is_err());
+    }
+
+    #[test]
+    fn test_sampled_segments() {
+        // Reference indexed every 3rd k-mer; the query follows one diagonal
+        // through the unsampled positions and then jumps to another
+        let kmer_map = vec![
+            Some(1),
+            None,
+            None,
+            Some(4),
+            None,
+            None,
+            Some(7),
+            None,
+            None,
+            Some(31),
+            None,
+            None,
+            None,
+        ];
+        let k = 5;
+        let alignments: Vec<Alignment> = Segments::sampled(kmer_map, k, 3)
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(
+            alignments,
+            vec![
+                Alignment::new(0, 11, 0, 11, true),
+                Alignment::new(9, 14, 30, 35, true)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sampled_quasi_align() {
+        use crate::KmerMap;
+        use bio_seq::prelude::*;
+
+        let reference = dna!("ACGTGACGGTCGTACCACCAAAGTTGCAATCGGATCCATGAACTGGA");
+        let query = &reference[4..40];
+
+        let full: KmerMap<7> = KmerMap::new(reference).unwrap();
+        let sampled: KmerMap<7> = KmerMap::sampled(reference, 4).unwrap();
+        assert!(sampled.index.len() < full.index.len());
+
+        let expected: Vec<Alignment> = full.quasi_align(query, 1);
+        let alignments: Vec<Alignment> = sampled.quasi_align(query, 1);
+        assert_eq!(expected.len(), 1);
+        assert_eq!(alignments.len(), 1);
+        assert_eq!(alignments[0].q_start(), expected[0].q_start());
+        assert_eq!(alignments[0].r_start(), expected[0].r_start());
+        assert!(alignments[0].q_end() + 4 > expected[0].q_end());
+    }
 }
@@ -0,0 +1,235 @@
+use std::collections::{HashMap, HashSet};
+
+use petgraph::visit::GraphBase;
+
+use bio_seq::prelude::*;
+
+use crate::graph::{Link, Unitigs, non_branching};
+use crate::{Debruijn, Node};
+
+/// De Bruijn graph of double-stranded DNA. A K-mer and its reverse
+/// complement are the same edge, counted under the smaller of the two, so a
+/// read and its reverse complement build the same graph. Through `Debruijn`
+/// the graph is the directed graph of both strands, where every edge has a
+/// twin on the other strand with the same multiplicity.
+#[derive(Clone, Default)]
+pub struct Bidirected<const K: usize> {
+    /// Counts of canonical K-mers
+    pub index: HashMap<Kmer<Dna, K>, usize>,
+    pub total: usize,
+}
+
+impl<const K: usize> GraphBase for Bidirected<K> {
+    type NodeId = Node<K>;
+    type EdgeId = Kmer<Dna, K>;
+}
+
+/// The smaller of a K-mer and its reverse complement, and whether that is
+/// the K-mer itself
+pub fn canonical<const K: usize>(kmer: Kmer<Dna, K>) -> (Kmer<Dna, K>, bool) {
+    let revcomp = kmer.to_revcomp();
+    if kmer <= revcomp {
+        (kmer, true)
+    } else {
+        (revcomp, false)
+    }
+}
+
+impl<const K: usize> Bidirected<K> {
+    pub fn add(&mut self, kmer: Kmer<Dna, K>) {
+        *self.index.entry(canonical(kmer).0).or_default() += 1;
+        self.total += 1;
+    }
+
+    /// Number of distinct edges up to reverse complement
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// Compact the graph into unitigs without a copy per strand. Each unitig
+    /// is spelled on the strand it was first reached from, links carry the
+    /// orientations of their ends and only one of a link and its reverse
+    /// complement is kept. Both orientations of a K-mer map to the offset
+    /// of the bases it covers in its unitig.
+    pub fn unitigs(&self) -> Unitigs<K> {
+        let mut unitigs: Unitigs<K> = Unitigs::default();
+        let mut visited: HashSet<Kmer<Dna, K>> = HashSet::new();
+        // first and last node of each unitig
+        let mut ends: Vec<(Node<K>, Node<K>)> = Vec::new();
+
+        let mut edges: Vec<Kmer<Dna, K>> = self.kmers().map(|(kmer, _)| kmer).collect();
+        edges.sort_unstable();
+
+        let heads = edges
+            .iter()
+            .filter(|kmer| !non_branching(self, Node::prefix(**kmer)));
+        let cycles = edges.iter();
+
+        for &first in heads.chain(cycles) {
+            if visited.contains(&canonical(first).0) {
+                continue;
+            }
+            let id = unitigs.graph.len();
+            let mut seq: Seq<Dna> = Node::prefix(first).to_seq();
+            let mut kmer = first;
+            let mut count: usize = 0;
+
+            loop {
+                visited.insert(canonical(kmer).0);
+                count += self.multiplicity(kmer);
+                let offset = seq.len() + 1 - K;
+                unitigs.positions.insert(kmer, (id, offset));
+                unitigs.positions.insert(kmer.to_revcomp(), (id, offset));
+                let node = Node::suffix(kmer);
+                seq.push(node.last());
+
+                if !non_branching(self, node) {
+                    break;
+                }
+                // a hairpin runs into its own reverse complement
+                match self.out_edges(node).next() {
+                    Some((next, _)) if !visited.contains(&canonical(next).0) => kmer = next,
+                    _ => break,
+                }
+            }
+
+            unitigs.graph.add(&seq);
+            unitigs.graph.counts.insert(id, count);
+            ends.push((Node::prefix(first), Node::suffix(kmer)));
+        }
+
+        // oriented unitigs by the node they start from
+        let mut starts: HashMap<Node<K>, Vec<(usize, bool)>> = HashMap::new();
+        for (id, &(first, last)) in ends.iter().enumerate() {
+            starts.entry(first).or_default().push((id, true));
+            starts.entry(last.revcomp()).or_default().push((id, false));
+        }
+
+        for (from, &(first, last)) in ends.iter().enumerate() {
+            for (from_forward, end) in [(true, last), (false, first.revcomp())] {
+                for &(to, to_forward) in starts.get(&end).into_iter().flatten() {
+                    // the same link read off the other strand
+                    let twin = (to, !to_forward, from, !from_forward);
+                    if (from, from_forward, to, to_forward) > twin {
+                        continue;
+                    }
+                    unitigs.graph.link(Link {
+                        from,
+                        from_forward,
+                        to,
+                        to_forward,
+                        overlap: K - 1,
+                    });
+                }
+            }
+        }
+        for links in unitigs.graph.graph.values_mut() {
+            links.sort_unstable_by_key(|link| (link.from_forward, link.to, link.to_forward));
+        }
+
+        unitigs
+    }
+}
+
+impl<const K: usize> Debruijn<K> for Bidirected<K> {
+    fn add_seq(&mut self, seq: &SeqSlice<Dna>) {
+        for kmer in seq.kmers() {
+            self.add(kmer);
+        }
+    }
+
+    /// Remove an edge together with its reverse complement
+    fn remove(&mut self, kmer: Kmer<Dna, K>) {
+        if let Some(count) = self.index.remove(&canonical(kmer).0) {
+            self.total = self.total.saturating_sub(count);
+        }
+    }
+
+    fn multiplicity(&self, kmer: Kmer<Dna, K>) -> usize {
+        self.index.get(&canonical(kmer).0).copied().unwrap_or(0)
+    }
+
+    /// Edges of both strands, a palindromic K-mer only once
+    fn kmers(&self) -> impl Iterator<Item = (Kmer<Dna, K>, usize)> + '_ {
+        self.index.iter().flat_map(|(&kmer, &m)| {
+            let revcomp = kmer.to_revcomp();
+            std::iter::once((kmer, m)).chain((revcomp != kmer).then_some((revcomp, m)))
+        })
+    }
+
+    /// Unitigs without a copy per strand, see `Bidirected::unitigs`
+    fn compress(&self) -> Unitigs<K> {
+        self.unitigs()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KmerTable;
+
+    fn check_links<const K: usize>(unitigs: &Unitigs<K>) {
+        for link in unitigs.graph.all_links() {
+            let from = unitigs.graph.oriented(link.from, link.from_forward);
+            let to = unitigs.graph.oriented(link.to, link.to_forward);
+            assert_eq!(&from[from.len() - link.overlap..], &to[..link.overlap]);
+        }
+    }
+
+    #[test]
+    fn test_bidirected_strands() {
+        let seq = dna!("ACGAAACTTGTTGGCCCAGTG");
+        let mut forward: Bidirected<6> = Bidirected::default();
+        let mut reverse: Bidirected<6> = Bidirected::default();
+        forward.add_seq(seq);
+        reverse.add_seq(&seq.to_revcomp());
+        assert_eq!(forward.index, reverse.index);
+
+        let kmer: Kmer<Dna, 6> = Kmer::try_from(dna!("AAACTT")).unwrap();
+        assert_eq!(forward.multiplicity(kmer), 1);
+        assert_eq!(forward.multiplicity(kmer.to_revcomp()), 1);
+        assert_eq!(forward.kmers().count(), 2 * forward.len());
+
+        // the directed graph of both strands has a unitig per strand
+        let mut directed: KmerTable<u32, 6> = KmerTable::default();
+        directed.add_seq(seq);
+        directed.add_seq(&seq.to_revcomp());
+        assert_eq!(directed.compress().graph.len(), 2);
+
+        let unitigs = forward.compress();
+        assert_eq!(unitigs.graph.len(), 1);
+        let unitig = unitigs.graph.index[0].to_string();
+        assert!(unitig == seq.to_string() || unitig == seq.to_revcomp().to_string());
+        assert_eq!(unitigs.positions.len(), 2 * forward.len());
+        assert_eq!(unitigs.get(kmer), unitigs.get(kmer.to_revcomp()));
+
+        forward.remove(kmer.to_revcomp());
+        assert_eq!(forward.multiplicity(kmer), 0);
+        assert_eq!(forward.total, 15);
+    }
+
+    #[test]
+    fn test_bidirected_links() {
+        // the second read is the reverse complement of a variant of the first
+        let mut graph: Bidirected<5> = Bidirected::default();
+        graph.add_seq(dna!("TTGACCTAAGGCATT"));
+        graph.add_seq(&dna!("TTGACCTCAGGCATT").to_revcomp());
+
+        let unitigs = graph.compress();
+        let mut seqs: Vec<String> = (0..unitigs.graph.len())
+            .map(|id| {
+                let forward = unitigs.graph.oriented(id, true).to_string();
+                let reverse = unitigs.graph.oriented(id, false).to_string();
+                forward.min(reverse)
+            })
+            .collect();
+        seqs.sort();
+        assert_eq!(seqs, vec!["AATGCCT", "ACCTAAGGC", "ACCTCAGGC", "AGGTCAA"]);
+        assert_eq!(unitigs.graph.edge_count(), 4);
+        check_links(&unitigs);
+    }
+}
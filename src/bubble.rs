@@ -0,0 +1,487 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+
+use bio_seq::prelude::*;
+
+use crate::error::Error;
+use crate::{Debruijn, Node};
+
+/// Most paths enumerated through one superbubble
+const MAX_BRANCHES: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Variant {
+    Snp,
+    Mnp,
+    Indel,
+}
+
+impl Variant {
+    fn as_str(self) -> &'static str {
+        match self {
+            Variant::Snp => "snp",
+            Variant::Mnp => "mnp",
+            Variant::Indel => "indel",
+        }
+    }
+}
+
+/// A path through a bubble with the colours that contain all of its edges
+#[derive(Clone, Debug, PartialEq)]
+pub struct Branch<const K: usize> {
+    pub nodes: Vec<Node<K>>,
+    pub colours: Vec<usize>,
+}
+
+impl<const K: usize> Branch<K> {
+    pub fn to_seq(&self) -> Seq<Dna> {
+        let mut seq: Seq<Dna> = self.nodes[0].to_seq();
+        seq.extend(self.nodes.iter().skip(1).map(|node| node.last()));
+        seq
+    }
+
+    /// The K-mer edges along the branch
+    pub fn kmers(&self) -> impl Iterator<Item = Kmer<Dna, K>> + '_ {
+        self.nodes
+            .windows(2)
+            .map(|pair| pair[0].extend(pair[1].last()))
+    }
+}
+
+/// A superbubble: every path from the source reaches the sink without
+/// leaving the bubble, and the paths in between are acyclic. A simple
+/// bubble is a superbubble with two single-path branches.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bubble<const K: usize> {
+    pub source: Node<K>,
+    pub sink: Node<K>,
+    pub branches: Vec<Branch<K>>,
+}
+
+impl<const K: usize> Bubble<K> {
+    /// Branch sequences without the suffix and prefix they all share, and
+    /// the length of that prefix. The suffix is stripped first so that
+    /// indels in repeats are shifted as far left as they go, and at least
+    /// the first base is always left in the prefix as an anchor.
+    pub fn alleles(&self) -> (usize, Vec<Seq<Dna>>) {
+        let seqs: Vec<Seq<Dna>> = self.branches.iter().map(Branch::to_seq).collect();
+        let shortest = seqs.iter().map(|seq| seq.len()).min().unwrap_or(0);
+
+        let from_end = |seq: &Seq<Dna>, i: usize| seq.get(seq.len() - 1 - i);
+        let mut suffix = 0;
+        while suffix + 1 < shortest
+            && seqs
+                .iter()
+                .all(|seq| from_end(seq, suffix) == from_end(&seqs[0], suffix))
+        {
+            suffix += 1;
+        }
+        // the prefix may not eat into the suffix of the shortest allele
+        let mut prefix = 0;
+        while prefix + suffix < shortest
+            && seqs
+                .iter()
+                .all(|seq| seq.get(prefix) == seqs[0].get(prefix))
+        {
+            prefix += 1;
+        }
+
+        let alleles = seqs
+            .iter()
+            .map(|seq| seq[prefix..seq.len() - suffix].to_owned())
+            .collect();
+        (prefix, alleles)
+    }
+
+    pub fn variant(&self) -> Variant {
+        let (_, alleles) = self.alleles();
+        if alleles
+            .iter()
+            .any(|allele| allele.len() != alleles[0].len())
+        {
+            Variant::Indel
+        } else if alleles[0].len() == 1 {
+            Variant::Snp
+        } else {
+            Variant::Mnp
+        }
+    }
+}
+
+fn successors<G: Debruijn<K>, const K: usize>(graph: &G, node: Node<K>) -> Vec<Node<K>> {
+    graph.successors(node).collect()
+}
+
+/// Sink of the superbubble starting at `source`, following Onodera et al.
+/// (2013), giving up once the bubble would have more than `max_nodes` nodes
+fn superbubble<G: Debruijn<K>, const K: usize>(
+    graph: &G,
+    source: Node<K>,
+    max_nodes: usize,
+) -> Option<Node<K>> {
+    let mut visited: HashSet<Node<K>> = HashSet::new();
+    let mut seen: HashSet<Node<K>> = HashSet::from([source]);
+    let mut stack: Vec<Node<K>> = vec![source];
+
+    while let Some(node) = stack.pop() {
+        visited.insert(node);
+        seen.remove(&node);
+        // the sink is still to come
+        if visited.len() + 1 > max_nodes {
+            return None;
+        }
+
+        let children = successors(graph, node);
+        if children.is_empty() {
+            // a tip
+            return None;
+        }
+        for child in children {
+            if child == source {
+                // a cycle through the source
+                return None;
+            }
+            seen.insert(child);
+            if graph
+                .predecessors(child)
+                .all(|pred| visited.contains(&pred))
+            {
+                stack.push(child);
+            }
+        }
+
+        if stack.len() == 1 && seen.len() == 1 {
+            let sink = stack[0];
+            if graph.successors(sink).any(|succ| succ == source) {
+                return None;
+            }
+            return Some(sink);
+        }
+    }
+    None
+}
+
+/// Every path from the source to the sink of a bubble, up to `MAX_BRANCHES`
+fn branches<G: Debruijn<K>, const K: usize>(
+    graph: &G,
+    source: Node<K>,
+    sink: Node<K>,
+) -> Vec<Vec<Node<K>>> {
+    let mut paths: Vec<Vec<Node<K>>> = Vec::new();
+    let mut path: Vec<Node<K>> = vec![source];
+    // successors of each node on the path still to try
+    let mut pending: Vec<Vec<Node<K>>> = vec![successors(graph, source)];
+
+    while let Some(next) = pending.last_mut() {
+        match next.pop() {
+            Some(node) if node == sink => {
+                let mut complete = path.clone();
+                complete.push(sink);
+                paths.push(complete);
+                if paths.len() == MAX_BRANCHES {
+                    break;
+                }
+            }
+            Some(node) => {
+                path.push(node);
+                pending.push(successors(graph, node));
+            }
+            None => {
+                path.pop();
+                pending.pop();
+            }
+        }
+    }
+    paths.reverse();
+    paths
+}
+
+/// Bubbles of at most `max_nodes` nodes, including the source and sink,
+/// with each branch coloured by the colours that contain all of its edges.
+/// `colours` gives the colour indices of each edge, as for
+/// `dot::write_debruijn`.
+pub fn bubbles<G, F, const K: usize>(graph: &G, max_nodes: usize, colours: F) -> Vec<Bubble<K>>
+where
+    G: Debruijn<K>,
+    F: Fn(Kmer<Dna, K>) -> Vec<usize>,
+{
+    let mut bubbles: Vec<Bubble<K>> = Vec::new();
+
+    for source in graph.nodes() {
+        if successors(graph, source).len() < 2 {
+            continue;
+        }
+        let Some(sink) = superbubble(graph, source, max_nodes) else {
+            continue;
+        };
+
+        let branches = branches(graph, source, sink)
+            .into_iter()
+            .map(|nodes| {
+                let mut branch = Branch {
+                    nodes,
+                    colours: Vec::new(),
+                };
+                let mut kmers = branch.kmers();
+                let mut shared = kmers.next().map(&colours).unwrap_or_default();
+                for kmer in kmers {
+                    let colours = colours(kmer);
+                    shared.retain(|c| colours.contains(c));
+                }
+                branch.colours = shared;
+                branch
+            })
+            .collect();
+
+        bubbles.push(Bubble {
+            source,
+            sink,
+            branches,
+        });
+    }
+    bubbles
+}
+
+/// Write bubbles as VCF records against the sequence of a reference
+/// colour. The reference allele of each bubble is the first branch the
+/// reference colour traverses, placed wherever the walk of `seq` enters the
+/// bubble at its source and follows that branch, so a bubble in a repeat
+/// gets a record per copy. Bubbles the reference does not traverse are
+/// skipped. Each sample gets the haploid genotype of the branch it
+/// traverses, or `.` if it traverses none or several, as in a repeat whose
+/// copies differ, since its colours do not tell which copy carries which
+/// allele. A `reference` that is not a sample is an `InvalidInput` error.
+pub fn write_vcf<W: Write, const K: usize>(
+    out: &mut W,
+    bubbles: &[Bubble<K>],
+    samples: &[String],
+    reference: usize,
+    seq: &SeqSlice<Dna>,
+) -> io::Result<()> {
+    let Some(chrom) = samples.get(reference) else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            Error::OutOfBounds {
+                index: reference,
+                bound: samples.len(),
+            },
+        ));
+    };
+
+    writeln!(out, "##fileformat=VCFv4.2")?;
+    writeln!(
+        out,
+        "##INFO=<ID=TYPE,Number=1,Type=String,Description=\"Variant type\">"
+    )?;
+    writeln!(
+        out,
+        "##FORMAT=<ID=GT,Number=1,Type=String,Description=\"Genotype\">"
+    )?;
+    write!(out, "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT")?;
+    for sample in samples {
+        write!(out, "\t{sample}")?;
+    }
+    writeln!(out)?;
+
+    // offsets of the K-mers along the reference walk
+    let walk: Vec<Kmer<Dna, K>> = seq.kmers().collect();
+    let mut offsets: HashMap<Kmer<Dna, K>, Vec<usize>> = HashMap::new();
+    for (offset, &kmer) in walk.iter().enumerate() {
+        offsets.entry(kmer).or_default().push(offset);
+    }
+    let mut records: Vec<(usize, String)> = Vec::new();
+
+    for bubble in bubbles {
+        let Some(ref_branch) = bubble
+            .branches
+            .iter()
+            .position(|branch| branch.colours.contains(&reference))
+        else {
+            continue;
+        };
+        let kmers: Vec<Kmer<Dna, K>> = bubble.branches[ref_branch].kmers().collect();
+        let entries: Vec<usize> = offsets
+            .get(&kmers[0])
+            .into_iter()
+            .flatten()
+            .copied()
+            .filter(|&offset| walk[offset..].starts_with(&kmers))
+            .collect();
+        if entries.is_empty() {
+            continue;
+        }
+
+        let (prefix, alleles) = bubble.alleles();
+        // the reference first, then the alternatives in branch order
+        let mut order: Vec<usize> = vec![ref_branch];
+        order.extend((0..alleles.len()).filter(|&i| i != ref_branch));
+
+        let variant = bubble.variant();
+        // indels are written with the preceding base as an anchor
+        let anchor = match variant {
+            Variant::Indel => 1,
+            _ => 0,
+        };
+        let start = prefix - anchor;
+        let allele = |i: usize| {
+            let branch = bubble.branches[i].to_seq();
+            let anchored: Seq<Dna> = branch[start..start + anchor].to_owned();
+            format!("{anchored}{}", alleles[i])
+        };
+        let alts: Vec<String> = order[1..].iter().map(|&i| allele(i)).collect();
+
+        let mut fields = format!(
+            "{}\t{}\t.\t.\tTYPE={}\tGT",
+            allele(ref_branch),
+            alts.join(","),
+            variant.as_str()
+        );
+        for colour in 0..samples.len() {
+            let traversed: Vec<usize> = order
+                .iter()
+                .enumerate()
+                .filter(|&(_, &i)| bubble.branches[i].colours.contains(&colour))
+                .map(|(genotype, _)| genotype)
+                .collect();
+            match traversed[..] {
+                [genotype] => fields.push_str(&format!("\t{genotype}")),
+                _ => fields.push_str("\t."),
+            }
+        }
+        for offset in entries {
+            let pos = offset + start + 1;
+            records.push((pos, format!("{chrom}\t{pos}\t.\t{fields}")));
+        }
+    }
+
+    records.sort();
+    for (_, record) in records {
+        writeln!(out, "{record}")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Coloured, KmerTable};
+
+    fn sorted_alleles(bubble: &Bubble<4>) -> Vec<String> {
+        let mut alleles: Vec<String> = bubble.alleles().1.iter().map(|a| a.to_string()).collect();
+        alleles.sort();
+        alleles
+    }
+
+    #[test]
+    fn test_bubble_variants() {
+        let mut graph: KmerTable<u32, 4> = KmerTable::default();
+        graph.add_seq(dna!("TTGACCGTAAG"));
+        graph.add_seq(dna!("TTGACTGTAAG"));
+        let found = bubbles(&graph, 16, |_| Vec::new());
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].source.to_string(), "GAC");
+        assert_eq!(found[0].sink.to_string(), "GTA");
+        assert_eq!(found[0].alleles().0, 3);
+        assert_eq!(sorted_alleles(&found[0]), vec!["C", "T"]);
+        assert_eq!(found[0].variant(), Variant::Snp);
+
+        let mut graph: KmerTable<u32, 4> = KmerTable::default();
+        graph.add_seq(dna!("TTGACCGTAAG"));
+        graph.add_seq(dna!("TTGAATGTAAG"));
+        let found = bubbles(&graph, 16, |_| Vec::new());
+        assert_eq!(sorted_alleles(&found[0]), vec!["AT", "CC"]);
+        assert_eq!(found[0].variant(), Variant::Mnp);
+
+        let mut graph: KmerTable<u32, 4> = KmerTable::default();
+        graph.add_seq(dna!("TTGACCGTAAG"));
+        graph.add_seq(dna!("TTGACGTAAG"));
+        let found = bubbles(&graph, 16, |_| Vec::new());
+        assert_eq!(sorted_alleles(&found[0]), vec!["", "C"]);
+        assert_eq!(found[0].variant(), Variant::Indel);
+
+        // too large for the limit
+        assert!(bubbles(&graph, 4, |_| Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn test_homopolymer_indel() {
+        // deleting one C of CCC is shifted to the start of the run
+        let mut graph: KmerTable<u32, 4> = KmerTable::default();
+        graph.add_seq(dna!("TTACCCGTAAG"));
+        graph.add_seq(dna!("TTACCGTAAG"));
+        let found = bubbles(&graph, 16, |_| Vec::new());
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].source.to_string(), "ACC");
+        assert_eq!(found[0].alleles().0, 1);
+        assert_eq!(sorted_alleles(&found[0]), vec!["", "C"]);
+        assert_eq!(found[0].variant(), Variant::Indel);
+    }
+
+    #[test]
+    fn test_superbubble() {
+        // two SNPs closer than K make one superbubble with four branches
+        let mut graph: KmerTable<u32, 4> = KmerTable::default();
+        graph.add_seq(dna!("TTGACCGTAAGC"));
+        graph.add_seq(dna!("TTGACTGTAAGC"));
+        graph.add_seq(dna!("TTGACCATAAGC"));
+        graph.add_seq(dna!("TTGACTATAAGC"));
+        let found = bubbles(&graph, 32, |_| Vec::new());
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].branches.len(), 4);
+        assert_eq!(found[0].variant(), Variant::Mnp);
+    }
+
+    #[test]
+    fn test_write_vcf() {
+        let mut graph: Coloured<4> = Coloured::default();
+        let reference = dna!("CCTTGACCGTAAGCATTCGGCTATCAG");
+        graph.add_sample("ref", reference);
+        graph.add_sample("x", dna!("CCTTGACTGTAAGCATTCGGCTATCAG"));
+        graph.add_sample("y", dna!("CCTTGACCGTAAGCATTCGGCTACAG"));
+
+        let found = graph.bubbles(16);
+        assert_eq!(found.len(), 2);
+
+        let mut out: Vec<u8> = Vec::new();
+        write_vcf(&mut out, &found, &graph.samples, 0, reference).unwrap();
+        let vcf = String::from_utf8(out).unwrap();
+        let records: Vec<&str> = vcf.lines().filter(|l| !l.starts_with('#')).collect();
+        assert_eq!(
+            records,
+            vec![
+                "ref\t8\t.\tC\tT\t.\t.\tTYPE=snp\tGT\t0\t1\t0",
+                "ref\t23\t.\tAT\tA\t.\t.\tTYPE=indel\tGT\t0\t0\t1",
+            ]
+        );
+        assert!(vcf.contains("#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\tref\tx\ty\n"));
+
+        let err = write_vcf(&mut Vec::new(), &found, &graph.samples, 3, reference).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_write_vcf_repeat() {
+        // the bubble sits in the second copy of a repeat, so x traverses
+        // both branches
+        let mut graph: Coloured<4> = Coloured::default();
+        let reference = dna!("TTGACCGTAAGCTCTTGACCGTAAGC");
+        graph.add_sample("ref", reference);
+        graph.add_sample("x", dna!("TTGACCGTAAGCTCTTGACTGTAAGC"));
+
+        let found = graph.bubbles(16);
+        let mut out: Vec<u8> = Vec::new();
+        write_vcf(&mut out, &found, &graph.samples, 0, reference).unwrap();
+        let vcf = String::from_utf8(out).unwrap();
+        let records: Vec<&str> = vcf.lines().filter(|l| !l.starts_with('#')).collect();
+        // a record per copy rather than only the first match of the branch,
+        // and no genotype for x, which carries C in one copy and T in the
+        // other
+        assert_eq!(
+            records,
+            vec![
+                "ref\t6\t.\tC\tT\t.\t.\tTYPE=snp\tGT\t0\t.",
+                "ref\t20\t.\tC\tT\t.\t.\tTYPE=snp\tGT\t0\t.",
+            ]
+        );
+    }
+}
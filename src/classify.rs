@@ -0,0 +1,211 @@
+use bio_seq::prelude::*;
+
+use crate::KmerMap;
+
+/// Outcome of classifying a single read against a panel of references
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Call {
+    /// Index of the reference that the read was assigned to
+    Classified(usize),
+    /// The best and runner-up references are too close to separate
+    Ambiguous,
+    /// No reference contains enough of the read's k-mers
+    Unclassified,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Classification {
+    pub call: Call,
+    /// Index and containment of the best and runner-up references
+    pub best: Option<(usize, f64)>,
+    pub runner_up: Option<(usize, f64)>,
+    /// Fraction of the read's k-mers contained in each reference, in panel order
+    pub containment: Vec<f64>,
+}
+
+/// Per-sample tally of read calls
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Abundance {
+    pub counts: Vec<usize>,
+    pub ambiguous: usize,
+    pub unclassified: usize,
+}
+
+impl Abundance {
+    pub fn total(&self) -> usize {
+        self.counts.iter().sum::<usize>() + self.ambiguous + self.unclassified
+    }
+
+    /// Fraction of all reads assigned to each reference
+    pub fn proportions(&self) -> Vec<f64> {
+        let total = self.total();
+        self.counts
+            .iter()
+            .map(|&c| {
+                if total > 0 {
+                    c as f64 / total as f64
+                } else {
+                    0.0
+                }
+            })
+            .collect()
+    }
+}
+
+/// A panel of named references that reads are screened against
+pub struct Classifier<const K: usize> {
+    pub names: Vec<String>,
+    pub references: Vec<KmerMap<K>>,
+    /// Minimum k-mer containment for the best reference to be called
+    pub min_containment: f64,
+    /// Minimum difference in containment between best and runner-up
+    pub min_margin: f64,
+}
+
+impl<const K: usize> Classifier<K> {
+    pub fn new(min_containment: f64, min_margin: f64) -> Self {
+        Classifier {
+            names: Vec::new(),
+            references: Vec::new(),
+            min_containment,
+            min_margin,
+        }
+    }
+
+    pub fn add(&mut self, name: &str, reference: KmerMap<K>) {
+        self.names.push(name.to_string());
+        self.references.push(reference);
+    }
+
+    pub fn len(&self) -> usize {
+        self.references.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.references.is_empty()
+    }
+
+    pub fn name(&self, call: Call) -> Option<&str> {
+        match call {
+            Call::Classified(i) => self.names.get(i).map(String::as_str),
+            _ => None,
+        }
+    }
+
+    pub fn classify(&self, seq: &SeqSlice<Dna>) -> Classification {
+        let containment: Vec<f64> = self
+            .references
+            .iter()
+            .map(|reference| {
+                let (matches, total) = reference.matches(seq);
+                if total > 0 {
+                    matches as f64 / total as f64
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+
+        let mut best: Option<(usize, f64)> = None;
+        let mut runner_up: Option<(usize, f64)> = None;
+
+        for (i, &c) in containment.iter().enumerate() {
+            match best {
+                Some((_, b)) if c <= b => {
+                    if runner_up.is_none_or(|(_, r)| c > r) {
+                        runner_up = Some((i, c));
+                    }
+                }
+                _ => {
+                    runner_up = best;
+                    best = Some((i, c));
+                }
+            }
+        }
+
+        let call = match (best, runner_up) {
+            (Some((_, b)), _) if b < self.min_containment || b == 0.0 => Call::Unclassified,
+            (Some((_, b)), Some((_, r))) if b - r < self.min_margin => Call::Ambiguous,
+            (Some((i, _)), _) => Call::Classified(i),
+            (None, _) => Call::Unclassified,
+        };
+
+        Classification {
+            call,
+            best,
+            runner_up,
+            containment,
+        }
+    }
+
+    /// Classify every read of a sample and tally the calls per reference
+    pub fn abundance<'a, I: IntoIterator<Item = &'a SeqSlice<Dna>>>(&self, reads: I) -> Abundance {
+        let mut abundance = Abundance {
+            counts: vec![0; self.len()],
+            ..Default::default()
+        };
+
+        for read in reads {
+            match self.classify(read).call {
+                Call::Classified(i) => abundance.counts[i] += 1,
+                Call::Ambiguous => abundance.ambiguous += 1,
+                Call::Unclassified => abundance.unclassified += 1,
+            }
+        }
+        abundance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn panel() -> Classifier<5> {
+        let mut classifier = Classifier::new(0.5, 0.1);
+        classifier.add("a", KmerMap::new(dna!("ACGTGACGGTCGTACCACCAAAGT")).unwrap());
+        classifier.add("b", KmerMap::new(dna!("TTGCAATCGGATCCATGAACTGGA")).unwrap());
+        classifier.add("c", KmerMap::new(dna!("ACGTGACGGTCGTACCAGGATTCA")).unwrap());
+        classifier
+    }
+
+    #[test]
+    fn test_classify_best_reference() {
+        let classifier = panel();
+
+        let result = classifier.classify(dna!("GCAATCGGATCCATG"));
+        assert_eq!(result.call, Call::Classified(1));
+        assert_eq!(classifier.name(result.call), Some("b"));
+        assert_eq!(result.containment[1], 1.0);
+        assert_eq!(result.best, Some((1, 1.0)));
+    }
+
+    #[test]
+    fn test_classify_ambiguous_and_unclassified() {
+        let classifier = panel();
+
+        // shared prefix of references a and c
+        let shared = classifier.classify(dna!("ACGTGACGGTCGTAC"));
+        assert_eq!(shared.call, Call::Ambiguous);
+        assert_eq!(shared.best.unwrap().1, shared.runner_up.unwrap().1);
+
+        let novel = classifier.classify(dna!("CCCCCCCCCCCC"));
+        assert_eq!(novel.call, Call::Unclassified);
+    }
+
+    #[test]
+    fn test_abundance() {
+        let classifier = panel();
+        let reads: [&SeqSlice<Dna>; 4] = [
+            dna!("GCAATCGGATCCATG"),
+            dna!("CGTACCACCAAAGT"),
+            dna!("ACGTGACGGTCGTAC"),
+            dna!("CCCCCCCCCCCC"),
+        ];
+
+        let abundance = classifier.abundance(reads);
+        assert_eq!(abundance.counts, vec![1, 1, 0]);
+        assert_eq!(abundance.ambiguous, 1);
+        assert_eq!(abundance.unclassified, 1);
+        assert_eq!(abundance.proportions(), vec![0.25, 0.25, 0.0]);
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::HashSet;
+
+use bio_seq::prelude::*;
+
+use crate::bubble;
+use crate::{Debruijn, Node};
+
+/// A simplification pass over a de Bruijn graph
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pass {
+    /// Remove edges with a multiplicity below this threshold
+    Abundance(usize),
+    /// Remove dead-end paths of at most this many edges that run into a
+    /// branching node
+    Tips(usize),
+    /// Keep only the branch with the highest mean multiplicity of bubbles
+    /// of at most this many nodes
+    Bubbles(usize),
+}
+
+/// Edges removed by a pass, with the multiplicity they had
+#[derive(Clone, Debug, PartialEq)]
+pub struct Removed<const K: usize> {
+    pub pass: Pass,
+    pub edges: Vec<(Kmer<Dna, K>, usize)>,
+}
+
+fn out_count<G: Debruijn<K>, const K: usize>(graph: &G, node: Node<K>) -> usize {
+    graph.out_edges(node).count()
+}
+
+fn in_count<G: Debruijn<K>, const K: usize>(graph: &G, node: Node<K>) -> usize {
+    graph.in_edges(node).count()
+}
+
+/// Edges of the tip hanging off a dead-end node, following out-edges if
+/// `forward` and in-edges otherwise, unless it is longer than `max_len` or
+/// does not run into a branching node
+fn tip<G: Debruijn<K>, const K: usize>(
+    graph: &G,
+    mut node: Node<K>,
+    forward: bool,
+    max_len: usize,
+) -> Option<Vec<Kmer<Dna, K>>> {
+    let mut kmers: Vec<Kmer<Dna, K>> = Vec::new();
+    loop {
+        let (kmer, next) = if forward {
+            let (kmer, _) = graph.out_edges(node).next()?;
+            (kmer, Node::suffix(kmer))
+        } else {
+            let (kmer, _) = graph.in_edges(node).next()?;
+            (kmer, Node::prefix(kmer))
+        };
+        kmers.push(kmer);
+        if kmers.len() > max_len {
+            return None;
+        }
+
+        let (inward, onward) = if forward {
+            (in_count(graph, next), out_count(graph, next))
+        } else {
+            (out_count(graph, next), in_count(graph, next))
+        };
+        match (inward, onward) {
+            (1, 1) => node = next,
+            // joins another path
+            (2.., _) => return Some(kmers),
+            _ => return None,
+        }
+    }
+}
+
+fn clip_tips<G: Debruijn<K>, const K: usize>(graph: &G, max_len: usize) -> Vec<Kmer<Dna, K>> {
+    let mut kmers: Vec<Kmer<Dna, K>> = Vec::new();
+    for node in graph.nodes() {
+        let (inward, outward) = (in_count(graph, node), out_count(graph, node));
+        if inward == 0 && outward == 1 {
+            kmers.extend(tip(graph, node, true, max_len).into_iter().flatten());
+        }
+        if inward == 1 && outward == 0 {
+            kmers.extend(tip(graph, node, false, max_len).into_iter().flatten());
+        }
+    }
+    kmers
+}
+
+fn pop_bubbles<G: Debruijn<K>, const K: usize>(graph: &G, max_nodes: usize) -> Vec<Kmer<Dna, K>> {
+    let mut removed: HashSet<Kmer<Dna, K>> = HashSet::new();
+
+    for bubble in bubble::bubbles(graph, max_nodes, |_| Vec::new()) {
+        // bubbles can share edges, skip those already popped
+        if bubble
+            .branches
+            .iter()
+            .flat_map(|branch| branch.kmers())
+            .any(|kmer| removed.contains(&kmer))
+        {
+            continue;
+        }
+
+        let coverage = |i: usize| {
+            let kmers: Vec<usize> = bubble.branches[i]
+                .kmers()
+                .map(|kmer| graph.multiplicity(kmer))
+                .collect();
+            kmers.iter().sum::<usize>() as f64 / kmers.len() as f64
+        };
+        let best = (0..bubble.branches.len())
+            .max_by(|&a, &b| coverage(a).total_cmp(&coverage(b)))
+            .expect("a bubble has at least two branches");
+        let kept: HashSet<Kmer<Dna, K>> = bubble.branches[best].kmers().collect();
+
+        for branch in &bubble.branches {
+            removed.extend(branch.kmers().filter(|kmer| !kept.contains(kmer)));
+        }
+    }
+    removed.into_iter().collect()
+}
+
+/// Run simplification passes in order, each on the output of the previous
+/// one, and report the edges each pass removed
+pub fn clean<G: Debruijn<K>, const K: usize>(graph: &mut G, passes: &[Pass]) -> Vec<Removed<K>> {
+    let mut report: Vec<Removed<K>> = Vec::new();
+
+    for &pass in passes {
+        let mut kmers: Vec<Kmer<Dna, K>> = match pass {
+            Pass::Abundance(min) => graph
+                .kmers()
+                .filter(|&(_, m)| m < min)
+                .map(|(kmer, _)| kmer)
+                .collect(),
+            Pass::Tips(max_len) => clip_tips(graph, max_len),
+            Pass::Bubbles(max_nodes) => pop_bubbles(graph, max_nodes),
+        };
+        kmers.sort_unstable();
+        kmers.dedup();
+
+        let edges = kmers
+            .into_iter()
+            .map(|kmer| {
+                let multiplicity = graph.multiplicity(kmer);
+                graph.remove(kmer);
+                (kmer, multiplicity)
+            })
+            .collect();
+        report.push(Removed { pass, edges });
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KmerTable;
+
+    const GENOME: &str = "ACGAAACTTGTTGGCCCAGTGTGAATCGCTTA";
+
+    fn reads() -> KmerTable<u32, 6> {
+        let genome: Seq<Dna> = Seq::from_str(GENOME).unwrap();
+        let mut graph: KmerTable<u32, 6> = KmerTable::default();
+        for _ in 0..5 {
+            graph.add_seq(&genome);
+        }
+        // a substitution in the middle of a read makes a bubble
+        graph.add_seq(dna!("GAAACTTGTTGGACCAGTGTGAATCG"));
+        // and one on its last base a tip
+        graph.add_seq(dna!("AACTTGTTGGCCA"));
+        // a read from elsewhere
+        graph.add_seq(dna!("TTTTTTCTTTT"));
+        graph
+    }
+
+    #[test]
+    fn test_clean() {
+        let mut graph = reads();
+        let report = clean(&mut graph, &[Pass::Tips(3), Pass::Bubbles(16)]);
+
+        assert_eq!(report[0].pass, Pass::Tips(3));
+        let tip: Vec<String> = report[0].edges.iter().map(|(k, _)| k.to_string()).collect();
+        assert_eq!(tip, vec!["TGGCCA"]);
+        // the six K-mers over the substitution
+        assert_eq!(report[1].edges.len(), 6);
+        assert!(report[1].edges.iter().all(|&(_, m)| m == 1));
+
+        let unitigs: Vec<String> = graph
+            .compress()
+            .graph
+            .index
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert!(unitigs.contains(&GENOME.to_string()));
+    }
+
+    #[test]
+    fn test_clean_abundance() {
+        let mut graph = reads();
+        let report = clean(&mut graph, &[Pass::Abundance(2), Pass::Tips(3)]);
+
+        // the edges of the bubble, the tip and the unrelated read
+        assert_eq!(report[0].edges.len(), 6 + 1 + 6);
+        assert!(report[1].edges.is_empty());
+        assert!(graph.kmers().all(|(_, m)| m >= 5));
+        assert_eq!(graph.compress().graph.len(), 1);
+    }
+}
@@ -0,0 +1,348 @@
+use std::collections::HashMap;
+use std::hash::{BuildHasher, RandomState};
+
+/// A set of colours as a bitvector of 64-bit words. Sparse sets store only
+/// their non-zero words with their offsets, so that a set of a few colours
+/// out of hundreds of samples takes a handful of bytes. Sets that would be
+/// smaller as a plain bitvector store every word up to the last non-zero
+/// one and no offsets. The layout depends only on the colours, so equal
+/// sets compare and hash equal.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ColourSet {
+    offsets: Vec<u32>,
+    words: Vec<u64>,
+}
+
+impl ColourSet {
+    pub fn new() -> Self {
+        ColourSet::default()
+    }
+
+    fn is_dense(&self) -> bool {
+        self.offsets.is_empty() && !self.words.is_empty()
+    }
+
+    /// Offsets and values of the non-zero words
+    fn nonzero(&self) -> impl Iterator<Item = (u32, u64)> + '_ {
+        let dense = self.is_dense();
+        self.words
+            .iter()
+            .enumerate()
+            .filter(|&(_, &word)| word != 0)
+            .map(move |(i, &word)| {
+                let offset = if dense { i as u32 } else { self.offsets[i] };
+                (offset, word)
+            })
+    }
+
+    /// Switch to whichever layout takes fewer bytes, sparse on a tie
+    fn relayout(&mut self) {
+        let (span, nonzero) = if self.is_dense() {
+            let nonzero = self.words.iter().filter(|&&word| word != 0).count();
+            (self.words.len(), nonzero)
+        } else {
+            match self.offsets.last() {
+                Some(&last) => (last as usize + 1, self.words.len()),
+                None => return,
+            }
+        };
+        let dense_bytes = span * size_of::<u64>();
+        let sparse_bytes = nonzero * (size_of::<u32>() + size_of::<u64>());
+        if (dense_bytes < sparse_bytes) == self.is_dense() {
+            return;
+        }
+        if self.is_dense() {
+            (self.offsets, self.words) = self.nonzero().unzip();
+        } else {
+            let mut words = vec![0; span];
+            for (&offset, &word) in self.offsets.iter().zip(&self.words) {
+                words[offset as usize] = word;
+            }
+            self.offsets = Vec::new();
+            self.words = words;
+        }
+    }
+
+    pub fn contains(&self, colour: usize) -> bool {
+        let offset = colour / 64;
+        let word = if self.is_dense() {
+            self.words.get(offset)
+        } else {
+            self.offsets
+                .binary_search(&(offset as u32))
+                .ok()
+                .map(|i| &self.words[i])
+        };
+        word.is_some_and(|word| word & (1 << (colour % 64)) != 0)
+    }
+
+    pub fn insert(&mut self, colour: usize) {
+        let offset = colour / 64;
+        let bit = 1 << (colour % 64);
+        if self.is_dense() {
+            if offset >= self.words.len() {
+                self.words.resize(offset + 1, 0);
+            }
+            self.words[offset] |= bit;
+        } else {
+            match self.offsets.binary_search(&(offset as u32)) {
+                Ok(i) => self.words[i] |= bit,
+                Err(i) => {
+                    self.offsets.insert(i, offset as u32);
+                    self.words.insert(i, bit);
+                }
+            }
+        }
+        self.relayout();
+    }
+
+    /// Colours in increasing order
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.nonzero().flat_map(|(offset, word)| {
+            (0..64)
+                .filter(move |bit| word & (1 << bit) != 0)
+                .map(move |bit| offset as usize * 64 + bit)
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.words
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// Heap bytes used by the set
+    pub fn bytes(&self) -> usize {
+        self.offsets.len() * size_of::<u32>() + self.words.len() * size_of::<u64>()
+    }
+}
+
+impl FromIterator<usize> for ColourSet {
+    fn from_iter<I: IntoIterator<Item = usize>>(colours: I) -> Self {
+        let mut set = ColourSet::new();
+        for colour in colours {
+            set.insert(colour);
+        }
+        set
+    }
+}
+
+/// No next class in a hash chain
+const END: u32 = u32::MAX;
+
+/// Distinct colour sets stored once and referred to by id. Class 0 is
+/// always the empty set. Classes are reference counted: `intern` and `with`
+/// hand out a reference, `release` gives one back, and a class without
+/// references is freed and its id reused.
+#[derive(Clone, Debug)]
+pub struct ColourClasses {
+    sets: Vec<ColourSet>,
+    /// References to each class
+    counts: Vec<usize>,
+    /// First class of each set hash, later classes with the same hash are
+    /// chained through `next`, so that each set is only stored once
+    ids: HashMap<u64, u32>,
+    next: Vec<u32>,
+    /// Ids of freed classes
+    free: Vec<u32>,
+    hasher: RandomState,
+}
+
+impl Default for ColourClasses {
+    fn default() -> Self {
+        let hasher = RandomState::new();
+        let empty = ColourSet::new();
+        ColourClasses {
+            ids: HashMap::from([(hasher.hash_one(&empty), 0)]),
+            sets: vec![empty],
+            counts: vec![0],
+            next: vec![END],
+            free: Vec::new(),
+            hasher,
+        }
+    }
+}
+
+impl ColourClasses {
+    fn find(&self, hash: u64, set: &ColourSet) -> Option<u32> {
+        let mut id = self.ids.get(&hash).copied().unwrap_or(END);
+        while id != END {
+            if &self.sets[id as usize] == set {
+                return Some(id);
+            }
+            id = self.next[id as usize];
+        }
+        None
+    }
+
+    /// Id of a colour set, storing it as a new class if it has not been
+    /// seen, and take a reference to it
+    pub fn intern(&mut self, set: ColourSet) -> u32 {
+        let hash = self.hasher.hash_one(&set);
+        let id = match self.find(hash, &set) {
+            Some(id) => id,
+            None => {
+                let head = self.ids.get(&hash).copied().unwrap_or(END);
+                let id = match self.free.pop() {
+                    Some(id) => {
+                        self.sets[id as usize] = set;
+                        self.next[id as usize] = head;
+                        id
+                    }
+                    None => {
+                        self.sets.push(set);
+                        self.counts.push(0);
+                        self.next.push(head);
+                        (self.sets.len() - 1) as u32
+                    }
+                };
+                self.ids.insert(hash, id);
+                id
+            }
+        };
+        self.counts[id as usize] += 1;
+        id
+    }
+
+    /// Give back a reference to a class, freeing it when it was the last.
+    /// The empty class is never freed.
+    pub fn release(&mut self, id: u32) {
+        let count = &mut self.counts[id as usize];
+        *count = count.saturating_sub(1);
+        if *count > 0 || id == 0 {
+            return;
+        }
+
+        let set = std::mem::take(&mut self.sets[id as usize]);
+        let hash = self.hasher.hash_one(&set);
+        let next = self.next[id as usize];
+        if self.ids[&hash] == id {
+            if next == END {
+                self.ids.remove(&hash);
+            } else {
+                self.ids.insert(hash, next);
+            }
+        } else {
+            let mut prev = self.ids[&hash];
+            while self.next[prev as usize] != id {
+                prev = self.next[prev as usize];
+            }
+            self.next[prev as usize] = next;
+        }
+        self.next[id as usize] = END;
+        self.free.push(id);
+    }
+
+    /// Id of the class with one more colour than an existing class. The
+    /// reference to `id` is moved to the returned class.
+    pub fn with(&mut self, id: u32, colour: usize) -> u32 {
+        let set = &self.sets[id as usize];
+        if set.contains(colour) {
+            return id;
+        }
+        let mut set = set.clone();
+        set.insert(colour);
+        let with = self.intern(set);
+        self.release(id);
+        with
+    }
+
+    pub fn get(&self, id: u32) -> &ColourSet {
+        &self.sets[id as usize]
+    }
+
+    /// Number of live classes, including the empty class
+    pub fn len(&self) -> usize {
+        self.sets.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Heap bytes used by the class sets, their reference counts and the
+    /// lookup table
+    pub fn bytes(&self) -> usize {
+        let sets: usize = self.sets.iter().map(ColourSet::bytes).sum();
+        let table = self.ids.capacity() * size_of::<(u64, u32)>();
+        sets + table
+            + self.sets.capacity() * size_of::<ColourSet>()
+            + self.counts.capacity() * size_of::<usize>()
+            + (self.next.capacity() + self.free.capacity()) * size_of::<u32>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_colour_set() {
+        let set: ColourSet = [3, 700, 1, 64, 3].into_iter().collect();
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![1, 3, 64, 700]);
+        assert_eq!(set.len(), 4);
+        assert!(set.contains(700));
+        assert!(!set.contains(699));
+        assert!(!set.contains(5000));
+        // three non-zero words out of eleven
+        assert_eq!(set.bytes(), 3 * 12);
+    }
+
+    #[test]
+    fn test_dense_colour_set() {
+        // a word at offset 0 is smaller without its offset
+        let mut set: ColourSet = [1, 2].into_iter().collect();
+        assert_eq!(set.bytes(), 8);
+
+        set.insert(700);
+        assert_eq!(set.bytes(), 2 * 12);
+        let dense: ColourSet = (0..256).step_by(3).collect();
+        assert_eq!(dense.bytes(), 4 * 8);
+        assert_eq!(dense.len(), 86);
+        assert!(dense.contains(255) && !dense.contains(254) && !dense.contains(300));
+
+        // the layout only depends on the colours
+        let mut reordered: ColourSet = [700].into_iter().collect();
+        for colour in dense.iter() {
+            reordered.insert(colour);
+        }
+        let mut grown = dense.clone();
+        grown.insert(700);
+        assert_eq!(reordered, grown);
+        assert_eq!(grown.iter().last(), Some(700));
+    }
+
+    #[test]
+    fn test_colour_classes() {
+        let mut classes = ColourClasses::default();
+        let a = classes.with(0, 5);
+        let b = classes.with(a, 9);
+        // {5} was only an intermediate step to {5, 9}
+        assert_eq!(classes.len(), 2);
+        assert_eq!(classes.intern([9, 5].into_iter().collect()), b);
+        assert_eq!(classes.with(b, 5), b);
+
+        let a = classes.with(0, 5);
+        assert_eq!(classes.len(), 3);
+        assert!(classes.get(0).is_empty());
+        assert_eq!(classes.get(b).iter().collect::<Vec<_>>(), vec![5, 9]);
+
+        // b is referred to twice
+        classes.release(b);
+        assert_eq!(classes.len(), 3);
+        classes.release(b);
+        assert_eq!(classes.len(), 2);
+        assert_eq!(classes.get(a).iter().collect::<Vec<_>>(), vec![5]);
+
+        // the freed id is reused
+        let c = classes.with(0, 1);
+        assert_eq!(c, b);
+        assert_eq!(classes.intern([5].into_iter().collect()), a);
+        assert!(classes.bytes() > 2 * 8);
+    }
+}
@@ -0,0 +1,329 @@
+use std::collections::{HashMap, HashSet};
+
+use petgraph::visit::GraphBase;
+
+use bio_seq::prelude::*;
+
+use crate::bubble::{self, Bubble};
+use crate::colour_class::{ColourClasses, ColourSet};
+use crate::graph::Unitigs;
+use crate::{Debruijn, Node, dot, graph};
+
+/// A de Bruijn graph whose edges carry the set of samples (colours) they
+/// occur in, as well as their multiplicity over all samples. Each distinct
+/// colour set is stored once as a class that K-mers refer to by id.
+#[derive(Clone)]
+pub struct Coloured<const K: usize> {
+    pub index: HashMap<Kmer<Dna, K>, usize>,
+    /// Colour class of each coloured K-mer
+    pub colours: HashMap<Kmer<Dna, K>, u32>,
+    pub classes: ColourClasses,
+    pub samples: Vec<String>,
+}
+
+/// Memory used by the colours of a graph
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ColourStats {
+    pub kmers: usize,
+    /// Classes referred to by at least one K-mer
+    pub classes: usize,
+    /// Bytes of the class sets and their lookup table
+    pub class_bytes: usize,
+    /// A class id plus an even share of the class sets
+    pub bytes_per_kmer: f64,
+}
+
+impl<const K: usize> GraphBase for Coloured<K> {
+    type NodeId = Node<K>;
+    type EdgeId = Kmer<Dna, K>;
+}
+
+impl<const K: usize> Default for Coloured<K> {
+    fn default() -> Self {
+        Coloured {
+            index: HashMap::new(),
+            colours: HashMap::new(),
+            classes: ColourClasses::default(),
+            samples: Vec::new(),
+        }
+    }
+}
+
+impl<const K: usize> Coloured<K> {
+    /// Index of a sample, adding it as a new colour if it is not known
+    pub fn sample(&mut self, name: &str) -> usize {
+        match self.colour(name) {
+            Some(colour) => colour,
+            None => {
+                self.samples.push(name.to_string());
+                self.samples.len() - 1
+            }
+        }
+    }
+
+    /// Index of a known sample
+    pub fn colour(&self, name: &str) -> Option<usize> {
+        self.samples.iter().position(|sample| sample == name)
+    }
+
+    pub fn add(&mut self, kmer: Kmer<Dna, K>, colour: usize) {
+        *self.index.entry(kmer).or_default() += 1;
+        let id = self.colours.entry(kmer).or_default();
+        *id = self.classes.with(*id, colour);
+    }
+
+    /// Add every K-mer of a sequence coloured by the sample it came from
+    pub fn add_sample(&mut self, name: &str, seq: &SeqSlice<Dna>) -> usize {
+        let colour = self.sample(name);
+        for kmer in seq.kmers() {
+            self.add(kmer, colour);
+        }
+        colour
+    }
+
+    /// Colour class of a K-mer, the empty class if it has no colours
+    pub fn class(&self, kmer: Kmer<Dna, K>) -> &ColourSet {
+        self.classes
+            .get(self.colours.get(&kmer).copied().unwrap_or(0))
+    }
+
+    /// Colours a K-mer occurs in, in increasing order
+    pub fn colours(&self, kmer: Kmer<Dna, K>) -> Vec<usize> {
+        self.class(kmer).iter().collect()
+    }
+
+    pub fn has_colour(&self, kmer: Kmer<Dna, K>, colour: usize) -> bool {
+        self.class(kmer).contains(colour)
+    }
+
+    /// Names of the samples a K-mer occurs in
+    pub fn sample_names(&self, kmer: Kmer<Dna, K>) -> Vec<&str> {
+        self.class(kmer)
+            .iter()
+            .map(|c| self.samples[c].as_str())
+            .collect()
+    }
+
+    /// Colours that contain every K-mer of a sequence, i.e. the samples in
+    /// which it could be spelled by a path
+    pub fn path_colours(&self, seq: &SeqSlice<Dna>) -> Vec<usize> {
+        let mut kmers = seq.kmers::<K>();
+        let Some(first) = kmers.next() else {
+            return Vec::new();
+        };
+        let mut shared: Vec<usize> = self.colours(first);
+        for kmer in kmers {
+            shared.retain(|&c| self.has_colour(kmer, c));
+            if shared.is_empty() {
+                break;
+            }
+        }
+        shared
+    }
+
+    /// Edges leaving a node that occur in a colour
+    pub fn out_edges_in(
+        &self,
+        node: Node<K>,
+        colour: usize,
+    ) -> impl Iterator<Item = (Kmer<Dna, K>, usize)> + '_ {
+        self.out_edges(node)
+            .filter(move |&(kmer, _)| self.has_colour(kmer, colour))
+    }
+
+    /// Edges entering a node that occur in a colour
+    pub fn in_edges_in(
+        &self,
+        node: Node<K>,
+        colour: usize,
+    ) -> impl Iterator<Item = (Kmer<Dna, K>, usize)> + '_ {
+        self.in_edges(node)
+            .filter(move |&(kmer, _)| self.has_colour(kmer, colour))
+    }
+
+    pub fn successors_in(
+        &self,
+        node: Node<K>,
+        colour: usize,
+    ) -> impl Iterator<Item = Node<K>> + '_ {
+        self.out_edges_in(node, colour)
+            .map(|(kmer, _)| Node::suffix(kmer))
+    }
+
+    pub fn predecessors_in(
+        &self,
+        node: Node<K>,
+        colour: usize,
+    ) -> impl Iterator<Item = Node<K>> + '_ {
+        self.in_edges_in(node, colour)
+            .map(|(kmer, _)| Node::prefix(kmer))
+    }
+
+    /// Compact into unitigs, each coloured by the union of the colours of
+    /// its K-mers
+    pub fn compress(&self) -> Unitigs<K> {
+        let mut unitigs = graph::compress(self);
+        for (kmer, (unitig, _)) in &unitigs.positions {
+            let colours = unitigs.graph.colours.entry(*unitig).or_default();
+            colours.extend(self.class(*kmer).iter());
+        }
+        for colours in unitigs.graph.colours.values_mut() {
+            colours.sort_unstable();
+            colours.dedup();
+        }
+        unitigs.graph.samples = self.samples.clone();
+        unitigs
+    }
+
+    /// Bubbles of at most `max_nodes` nodes with the colours of each
+    /// branch, see `bubble::bubbles`
+    pub fn bubbles(&self, max_nodes: usize) -> Vec<Bubble<K>> {
+        bubble::bubbles(self, max_nodes, |kmer| self.colours(kmer))
+    }
+
+    /// DOT source with edges and nodes filled by their colours
+    pub fn to_dot(&self) -> String {
+        dot::debruijn(self, |kmer| self.colours(kmer))
+    }
+
+    pub fn stats(&self) -> ColourStats {
+        let kmers = self.index.len();
+        let referenced: HashSet<u32> = self.colours.values().copied().collect();
+        let class_bytes = self.classes.bytes();
+        let bytes_per_kmer = if kmers > 0 {
+            (kmers * size_of::<u32>() + class_bytes) as f64 / kmers as f64
+        } else {
+            0.0
+        };
+
+        ColourStats {
+            kmers,
+            classes: referenced.len(),
+            class_bytes,
+            bytes_per_kmer,
+        }
+    }
+}
+
+impl<const K: usize> Debruijn<K> for Coloured<K> {
+    /// Add K-mers without a colour; see `add_sample`
+    fn add_seq(&mut self, seq: &SeqSlice<Dna>) {
+        for kmer in seq.kmers() {
+            *self.index.entry(kmer).or_default() += 1;
+        }
+    }
+
+    fn remove(&mut self, kmer: Kmer<Dna, K>) {
+        self.index.remove(&kmer);
+        if let Some(id) = self.colours.remove(&kmer) {
+            self.classes.release(id);
+        }
+    }
+
+    fn multiplicity(&self, kmer: Kmer<Dna, K>) -> usize {
+        self.index.get(&kmer).copied().unwrap_or(0)
+    }
+
+    fn kmers(&self) -> impl Iterator<Item = (Kmer<Dna, K>, usize)> + '_ {
+        self.index.iter().map(|(kmer, count)| (*kmer, *count))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coloured() -> Coloured<4> {
+        let mut graph: Coloured<4> = Coloured::default();
+        graph.add_sample("x", dna!("ACGTTACG"));
+        graph.add_sample("y", dna!("ACGTAACG"));
+        graph
+    }
+
+    #[test]
+    fn test_colour_queries() {
+        let graph = coloured();
+        let kmer = |s: &SeqSlice<Dna>| -> Kmer<Dna, 4> { Kmer::try_from(s).unwrap() };
+
+        assert_eq!(graph.samples, vec!["x", "y"]);
+        assert_eq!(graph.colours(kmer(dna!("ACGT"))), &[0, 1]);
+        assert_eq!(graph.colours(kmer(dna!("GTTA"))), &[0]);
+        assert_eq!(graph.sample_names(kmer(dna!("GTAA"))), vec!["y"]);
+        assert_eq!(graph.multiplicity(kmer(dna!("ACGT"))), 2);
+        assert!(graph.colours(kmer(dna!("GGGG"))).is_empty());
+
+        assert_eq!(graph.path_colours(dna!("ACGTT")), vec![0]);
+        assert_eq!(graph.path_colours(dna!("AACGT")), vec![1]);
+        assert_eq!(graph.path_colours(dna!("TTACGTAA")), Vec::<usize>::new());
+        assert_eq!(graph.colour("y"), Some(1));
+    }
+
+    #[test]
+    fn test_colour_neighbours() {
+        let graph = coloured();
+        let node = Node::try_from(dna!("CGT")).unwrap();
+
+        let all: Vec<String> = graph.successors(node).map(|n| n.to_string()).collect();
+        let x: Vec<String> = graph
+            .successors_in(node, 0)
+            .map(|n| n.to_string())
+            .collect();
+        let y: Vec<String> = graph
+            .successors_in(node, 1)
+            .map(|n| n.to_string())
+            .collect();
+        assert_eq!(all, vec!["GTA", "GTT"]);
+        assert_eq!(x, vec!["GTT"]);
+        assert_eq!(y, vec!["GTA"]);
+
+        let node = Node::try_from(dna!("TAC")).unwrap();
+        assert_eq!(graph.predecessors_in(node, 0).count(), 1);
+        assert_eq!(graph.predecessors_in(node, 1).count(), 0);
+    }
+
+    #[test]
+    fn test_coloured_unitigs() {
+        let unitigs = coloured().compress();
+        let colours = |seq: &str| {
+            let node = unitigs
+                .graph
+                .index
+                .iter()
+                .position(|s| s.to_string() == seq)
+                .unwrap();
+            unitigs.graph.colours[&node].clone()
+        };
+
+        assert_eq!(colours("CGTTACG"), vec![0]);
+        assert_eq!(colours("CGTAACG"), vec![1]);
+        assert_eq!(colours("ACGT"), vec![0, 1]);
+        assert_eq!(unitigs.graph.samples, vec!["x", "y"]);
+    }
+
+    #[test]
+    fn test_colour_classes() {
+        let mut graph = coloured();
+        graph.add_sample("z", dna!("ACGTTACG"));
+
+        // {x, y, z}, {x, z}, {y} and the empty class; the intermediate {x}
+        // and {x, y} were freed once no K-mer referred to them
+        assert_eq!(graph.classes.len(), 4);
+
+        let kmer: Kmer<Dna, 4> = Kmer::try_from(dna!("GTTA")).unwrap();
+        assert_eq!(graph.colours(kmer), vec![0, 2]);
+
+        let stats = graph.stats();
+        assert_eq!(stats.kmers, 9);
+        assert_eq!(stats.classes, 3);
+        assert_eq!(stats.class_bytes, graph.classes.bytes());
+        assert!(stats.class_bytes > 3 * 8);
+        assert_eq!(
+            stats.bytes_per_kmer,
+            (9.0 * 4.0 + stats.class_bytes as f64) / 9.0
+        );
+
+        // removing the only K-mer of {x, y, z} frees its class
+        graph.remove(Kmer::try_from(dna!("ACGT")).unwrap());
+        assert_eq!(graph.classes.len(), 3);
+    }
+}
@@ -0,0 +1,211 @@
+use std::collections::HashSet;
+
+use bio_seq::prelude::*;
+
+use crate::graph::non_branching;
+use crate::{Debruijn, Node};
+
+/// Differences between two de Bruijn graphs over the same K, e.g. of two
+/// strains or from two assemblers
+#[derive(Clone, Debug, Default)]
+pub struct Comparison<const K: usize> {
+    /// Number of edges in both graphs
+    pub shared_edges: usize,
+    /// Edges only in the first graph, sorted
+    pub only_first: Vec<Kmer<Dna, K>>,
+    /// Edges only in the second graph, sorted
+    pub only_second: Vec<Kmer<Dna, K>>,
+    pub shared_nodes: usize,
+    pub nodes_only_first: usize,
+    pub nodes_only_second: usize,
+    /// Unitigs of the first graph that are not unitigs of the second
+    pub unitigs_only_first: Vec<Seq<Dna>>,
+    /// Unitigs of the second graph that are not unitigs of the first
+    pub unitigs_only_second: Vec<Seq<Dna>>,
+    /// Fraction of the steps from one edge to the next along the unitigs
+    /// of both graphs that the other graph can not take without branching:
+    /// it lacks one of the two edges, or the node between them branches.
+    /// A substitution breaks the steps over its K-mers and a new branch the
+    /// steps through the node it leaves from, so the score grows with the
+    /// size of the change rather than with the unitigs it touches.
+    pub divergence: f64,
+}
+
+impl<const K: usize> Comparison<K> {
+    /// Jaccard similarity of the edge sets
+    pub fn jaccard(&self) -> f64 {
+        let union = self.shared_edges + self.only_first.len() + self.only_second.len();
+        if union > 0 {
+            self.shared_edges as f64 / union as f64
+        } else {
+            1.0
+        }
+    }
+}
+
+/// Sorted edges of `graph` that `other` lacks
+fn missing<G: Debruijn<K>, H: Debruijn<K>, const K: usize>(
+    graph: &G,
+    other: &H,
+) -> Vec<Kmer<Dna, K>> {
+    let mut kmers: Vec<Kmer<Dna, K>> = graph
+        .kmers()
+        .filter(|&(kmer, _)| other.multiplicity(kmer) == 0)
+        .map(|(kmer, _)| kmer)
+        .collect();
+    kmers.sort_unstable();
+    kmers
+}
+
+/// Steps between consecutive edges along a set of unitigs, and how many of
+/// them `other` can not take: it lacks either edge or branches at the node
+/// in between
+fn broken_steps<H: Debruijn<K>, const K: usize>(unitigs: &[Seq<Dna>], other: &H) -> (usize, usize) {
+    let mut steps = 0;
+    let mut broken = 0;
+    for unitig in unitigs {
+        let kmers: Vec<Kmer<Dna, K>> = unitig.kmers().collect();
+        for pair in kmers.windows(2) {
+            steps += 1;
+            if other.multiplicity(pair[0]) == 0
+                || other.multiplicity(pair[1]) == 0
+                || !non_branching(other, Node::suffix(pair[0]))
+            {
+                broken += 1;
+            }
+        }
+    }
+    (steps, broken)
+}
+
+/// Compare the edges, nodes and unitigs of two graphs. Edge multiplicities
+/// are ignored.
+pub fn compare<G: Debruijn<K>, H: Debruijn<K>, const K: usize>(
+    first: &G,
+    second: &H,
+) -> Comparison<K> {
+    let only_first = missing(first, second);
+    let only_second = missing(second, first);
+
+    let nodes_first: HashSet<Node<K>> = first.nodes().into_iter().collect();
+    let nodes_second: HashSet<Node<K>> = second.nodes().into_iter().collect();
+    let shared_nodes = nodes_first.intersection(&nodes_second).count();
+
+    let unitigs_first = first.compress().graph.index;
+    let unitigs_second = second.compress().graph.index;
+    let set_first: HashSet<&Seq<Dna>> = unitigs_first.iter().collect();
+    let set_second: HashSet<&Seq<Dna>> = unitigs_second.iter().collect();
+
+    let unitigs_only_first: Vec<Seq<Dna>> = unitigs_first
+        .iter()
+        .filter(|seq| !set_second.contains(seq))
+        .cloned()
+        .collect();
+    let unitigs_only_second: Vec<Seq<Dna>> = unitigs_second
+        .iter()
+        .filter(|seq| !set_first.contains(seq))
+        .cloned()
+        .collect();
+
+    let (steps_first, broken_first) = broken_steps(&unitigs_first, second);
+    let (steps_second, broken_second) = broken_steps(&unitigs_second, first);
+    let total = steps_first + steps_second;
+    let divergent = broken_first + broken_second;
+
+    Comparison {
+        shared_edges: first.kmers().count() - only_first.len(),
+        only_first,
+        only_second,
+        shared_nodes,
+        nodes_only_first: nodes_first.len() - shared_nodes,
+        nodes_only_second: nodes_second.len() - shared_nodes,
+        unitigs_only_first,
+        unitigs_only_second,
+        divergence: if total > 0 {
+            divergent as f64 / total as f64
+        } else {
+            0.0
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KmerTable;
+
+    const GENOME: &str = "ACGAAACTTGTTGGCCCAGTGTGAATCGCTTA";
+    const VARIANT: &str = "ACGAAACTTGTTGGCACAGTGTGAATCGCTTA";
+    const PLASMID: &str = "GATTACAGCTTGACCA";
+
+    fn graph(seqs: &[&str]) -> KmerTable<u32, 6> {
+        let mut graph: KmerTable<u32, 6> = KmerTable::default();
+        for seq in seqs {
+            graph.add_seq(&Seq::from_str(seq).unwrap());
+        }
+        graph
+    }
+
+    #[test]
+    fn test_compare() {
+        let first = graph(&[GENOME, PLASMID]);
+        let second = graph(&[VARIANT, PLASMID, PLASMID]);
+
+        let same = first.compare(&first);
+        assert!(same.only_first.is_empty() && same.unitigs_only_second.is_empty());
+        assert_eq!(same.divergence, 0.0);
+        assert_eq!(same.jaccard(), 1.0);
+
+        let comparison = first.compare(&second);
+        // the six K-mers over the substitution
+        assert_eq!(comparison.shared_edges, 21 + 11);
+        assert_eq!(comparison.only_first.len(), 6);
+        assert_eq!(comparison.only_second.len(), 6);
+        assert_eq!(comparison.shared_nodes, 23 + 12);
+        assert_eq!(comparison.nodes_only_first, 5);
+        assert_eq!(comparison.nodes_only_second, 5);
+
+        assert_eq!(comparison.unitigs_only_first.len(), 1);
+        assert_eq!(comparison.unitigs_only_first[0].to_string(), GENOME);
+        assert_eq!(comparison.unitigs_only_second[0].to_string(), VARIANT);
+        // seven steps over the six K-mers of the substitution in each graph
+        assert_eq!(comparison.divergence, 14.0 / 72.0);
+        assert_eq!(comparison.jaccard(), 32.0 / 44.0);
+    }
+
+    #[test]
+    fn test_compare_branch() {
+        // a branch off the genome splits it into unitigs
+        let first = graph(&[GENOME]);
+        let second = graph(&[GENOME, "TTGTTGGAA"]);
+
+        let comparison = compare(&first, &second);
+        assert!(comparison.only_first.is_empty());
+        assert_eq!(comparison.only_second.len(), 2);
+        assert_eq!(comparison.unitigs_only_first.len(), 1);
+        assert_eq!(comparison.unitigs_only_second.len(), 3);
+        // the step through the branching node and the step along the new
+        // branch, not the whole genome the branch splits
+        assert_eq!(comparison.divergence, 2.0 / 52.0);
+    }
+
+    #[test]
+    fn test_compare_small_difference() {
+        // one substitution in a long pseudo-random genome
+        let mut state: u64 = 7;
+        let genome: String = (0..400)
+            .map(|_| {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+                ['A', 'C', 'G', 'T'][(state >> 62) as usize]
+            })
+            .collect();
+        let base = if &genome[200..201] == "A" { "C" } else { "A" };
+        let variant = format!("{}{base}{}", &genome[..200], &genome[201..]);
+
+        let first = graph(&[&genome]);
+        let comparison = compare(&first, &graph(&[&variant]));
+        assert!(comparison.divergence > 0.0);
+        assert!(comparison.divergence < 0.05);
+        assert!(comparison.divergence < compare(&first, &graph(&[PLASMID])).divergence);
+    }
+}
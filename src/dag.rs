@@ -0,0 +1,229 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use petgraph::visit::GraphBase;
+
+use bio_seq::prelude::*;
+
+use crate::debruijn::BASES;
+use crate::error::{Error, Result};
+use crate::{Debruijn, Node};
+
+/// The acyclic part of a de Bruijn graph between two nodes
+#[derive(Clone, Debug)]
+pub struct Dag<const K: usize> {
+    pub start: Node<K>,
+    pub end: Node<K>,
+    /// Edges and their multiplicities in the source graph
+    pub edges: HashMap<Kmer<Dna, K>, usize>,
+    /// Back edges removed to break cycles, in the order they were found
+    pub cut: Vec<Kmer<Dna, K>>,
+}
+
+impl<const K: usize> GraphBase for Dag<K> {
+    type NodeId = Node<K>;
+    type EdgeId = Kmer<Dna, K>;
+}
+
+impl<const K: usize> Debruijn<K> for Dag<K> {
+    /// Add K-mers as edges without checking that the graph stays acyclic
+    fn add_seq(&mut self, seq: &SeqSlice<Dna>) {
+        for kmer in seq.kmers() {
+            *self.edges.entry(kmer).or_default() += 1;
+        }
+    }
+
+    fn remove(&mut self, kmer: Kmer<Dna, K>) {
+        self.edges.remove(&kmer);
+    }
+
+    fn multiplicity(&self, kmer: Kmer<Dna, K>) -> usize {
+        self.edges.get(&kmer).copied().unwrap_or(0)
+    }
+
+    fn kmers(&self) -> impl Iterator<Item = (Kmer<Dna, K>, usize)> + '_ {
+        self.edges.iter().map(|(kmer, m)| (*kmer, *m))
+    }
+}
+
+impl<const K: usize> Dag<K> {
+    /// Nodes reachable from the start such that every edge goes from an
+    /// earlier to a later node
+    pub fn toposort(&self) -> Vec<Node<K>> {
+        let mut order: Vec<Node<K>> = Vec::new();
+        let mut visited: HashSet<Node<K>> = HashSet::from([self.start]);
+        // nodes with the bases of the successors still to visit
+        let mut stack: Vec<(Node<K>, usize)> = vec![(self.start, 0)];
+
+        while let Some((node, next)) = stack.last_mut() {
+            let node = *node;
+            match BASES[*next..]
+                .iter()
+                .position(|&base| self.multiplicity(node.extend(base)) > 0)
+            {
+                Some(i) => {
+                    let base = BASES[*next + i];
+                    *next += i + 1;
+                    let succ = Node::suffix(node.extend(base));
+                    if visited.insert(succ) {
+                        stack.push((succ, 0));
+                    }
+                }
+                None => {
+                    order.push(node);
+                    stack.pop();
+                }
+            }
+        }
+        order.reverse();
+        order
+    }
+}
+
+/// Nodes reachable from `from` in a direction without leaving through
+/// `stop`
+fn reachable<const K: usize, I: Iterator<Item = Node<K>>>(
+    from: Node<K>,
+    stop: Node<K>,
+    neighbours: impl Fn(Node<K>) -> I,
+) -> HashSet<Node<K>> {
+    let mut seen: HashSet<Node<K>> = HashSet::from([from]);
+    let mut queue: VecDeque<Node<K>> = VecDeque::from([from]);
+    while let Some(node) = queue.pop_front() {
+        if node == stop {
+            continue;
+        }
+        for next in neighbours(node) {
+            if seen.insert(next) {
+                queue.push_back(next);
+            }
+        }
+    }
+    seen
+}
+
+/// Extract every path from `start` to `end` as a DAG. Edges into `start`
+/// and out of `end` are dropped, then a depth-first search from `start`
+/// cuts the back edges of any remaining cycles. Nodes that could only reach
+/// `end` through a cut edge are kept as dead ends.
+pub fn subdawg<G: Debruijn<K>, const K: usize>(
+    graph: &G,
+    start: Node<K>,
+    end: Node<K>,
+) -> Result<Dag<K>> {
+    if start == end {
+        return Err(Error::NoPath(format!("start and end are both {start}")));
+    }
+    let forward = reachable(start, end, |node| {
+        graph.successors(node).filter(move |&succ| succ != start)
+    });
+    if !forward.contains(&end) {
+        return Err(Error::NoPath(format!(
+            "{end} is not reachable from {start}"
+        )));
+    }
+    let backward = reachable(end, start, |node| {
+        graph.predecessors(node).filter(move |&pred| pred != end)
+    });
+
+    let mut dag = Dag {
+        start,
+        end,
+        edges: HashMap::new(),
+        cut: Vec::new(),
+    };
+
+    // iterative DFS colouring nodes on the stack to find back edges
+    let mut on_stack: HashSet<Node<K>> = HashSet::from([start]);
+    let mut done: HashSet<Node<K>> = HashSet::new();
+    let mut stack: Vec<(Node<K>, usize)> = vec![(start, 0)];
+
+    while let Some((node, next)) = stack.last_mut() {
+        let node = *node;
+        if node == end || *next == BASES.len() {
+            stack.pop();
+            on_stack.remove(&node);
+            done.insert(node);
+            continue;
+        }
+        let kmer = node.extend(BASES[*next]);
+        *next += 1;
+
+        let multiplicity = graph.multiplicity(kmer);
+        let succ = Node::suffix(kmer);
+        if multiplicity == 0
+            || succ == start
+            || !forward.contains(&succ)
+            || !backward.contains(&succ)
+        {
+            continue;
+        }
+        if on_stack.contains(&succ) {
+            dag.cut.push(kmer);
+            continue;
+        }
+        dag.edges.insert(kmer, multiplicity);
+        if !done.contains(&succ) {
+            on_stack.insert(succ);
+            stack.push((succ, 0));
+        }
+    }
+
+    Ok(dag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KmerTable;
+
+    fn node(seq: &SeqSlice<Dna>) -> Node<4> {
+        Node::try_from(seq).unwrap()
+    }
+
+    #[test]
+    fn test_subdawg_bubble() {
+        let mut graph: KmerTable<u32, 4> = KmerTable::default();
+        graph.add_seq(dna!("GGACTTTGCC"));
+        graph.add_seq(dna!("GGACAATGCC"));
+        // leaves the bubble and never comes back
+        graph.add_seq(dna!("CTTTAA"));
+
+        assert!(matches!(
+            graph.subdawg(node(dna!("GAC")), node(dna!("GAC"))),
+            Err(Error::NoPath(_))
+        ));
+
+        let dag = graph.subdawg(node(dna!("GGA")), node(dna!("GCC"))).unwrap();
+        assert!(dag.cut.is_empty());
+        assert_eq!(dag.edges.len(), 12);
+        assert_eq!(dag.multiplicity(Kmer::try_from(dna!("GGAC")).unwrap()), 2);
+        assert_eq!(dag.multiplicity(Kmer::try_from(dna!("TTTA")).unwrap()), 0);
+
+        let order = dag.toposort();
+        assert_eq!(order.len(), dag.nodes().len());
+        assert_eq!(order.first(), Some(&dag.start));
+        assert_eq!(order.last(), Some(&dag.end));
+        let position: HashMap<Node<4>, usize> =
+            order.iter().enumerate().map(|(i, n)| (*n, i)).collect();
+        for (kmer, _) in dag.kmers() {
+            assert!(position[&Node::prefix(kmer)] < position[&Node::suffix(kmer)]);
+        }
+    }
+
+    #[test]
+    fn test_subdawg_cycle() {
+        // CGTT -> GTTC -> TTCG -> TCGT closes a loop through CGT
+        let mut graph: KmerTable<u32, 4> = KmerTable::default();
+        graph.add_seq(dna!("AACGTTCGTAA"));
+
+        let dag = graph.subdawg(node(dna!("AAC")), node(dna!("TAA"))).unwrap();
+        let cut: Vec<String> = dag.cut.iter().map(|k| k.to_string()).collect();
+        assert_eq!(cut, vec!["TCGT"]);
+        assert_eq!(dag.toposort().len(), dag.nodes().len());
+
+        assert!(matches!(
+            graph.subdawg(node(dna!("TAA")), node(dna!("AAC"))),
+            Err(Error::NoPath(_))
+        ));
+    }
+}
@@ -0,0 +1,328 @@
+use core::fmt;
+
+use bio_seq::prelude::*;
+
+use crate::clean::{self, Pass, Removed};
+use crate::compare::{self, Comparison};
+use crate::dag::{self, Dag};
+use crate::dot;
+use crate::error::{Error, Result};
+use crate::eulerian::{self, Path, VirtualEdge};
+use crate::graph::{self, Unitigs};
+use crate::matrix::{self, Coo};
+
+pub(crate) const BASES: [Dna; 4] = [Dna::A, Dna::C, Dna::G, Dna::T];
+
+/// A (K-1)-mer node of a de Bruijn graph whose edges are K-mers. Bases are
+/// packed two bits each with the first base in the lowest bits, the same
+/// layout as a `Kmer<Dna, K>`, so that nodes and edges convert with shifts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Node<const K: usize> {
+    bs: usize,
+}
+
+impl<const K: usize> Node<K> {
+    const BITS: usize = (K - 1) * Dna::BITS as usize;
+
+    fn mask() -> usize {
+        (1 << Self::BITS) - 1
+    }
+
+    /// The first K-1 bases of an edge
+    pub fn prefix(kmer: Kmer<Dna, K>) -> Self {
+        Node {
+            bs: kmer.bs & Self::mask(),
+        }
+    }
+
+    /// The last K-1 bases of an edge
+    pub fn suffix(kmer: Kmer<Dna, K>) -> Self {
+        Node {
+            bs: kmer.bs >> Dna::BITS,
+        }
+    }
+
+    /// The edge leaving this node by appending a base
+    pub fn extend(self, base: Dna) -> Kmer<Dna, K> {
+        Kmer::from(self.bs | ((base as usize) << Self::BITS))
+    }
+
+    /// The edge entering this node by prepending a base
+    pub fn prepend(self, base: Dna) -> Kmer<Dna, K> {
+        Kmer::from((self.bs << Dna::BITS) | base as usize)
+    }
+
+    pub fn nth(&self, i: usize) -> Dna {
+        Dna::unsafe_from_bits(((self.bs >> (i * Dna::BITS as usize)) & 0b11) as u8)
+    }
+
+    pub fn last(&self) -> Dna {
+        self.nth(K - 2)
+    }
+
+    pub fn to_seq(&self) -> Seq<Dna> {
+        (0..K - 1).map(|i| self.nth(i)).collect()
+    }
+
+    /// The same (K-1)-mer read off the other strand
+    pub fn revcomp(self) -> Self {
+        let bs = (0..K - 1).fold(0, |bs, i| {
+            let comp = self.nth(i) as usize ^ 0b11;
+            bs | comp << ((K - 2 - i) * Dna::BITS as usize)
+        });
+        Node { bs }
+    }
+
+    /// The smaller of the node and its reverse complement, and whether that
+    /// is the node itself
+    pub fn canonical(self) -> (Self, bool) {
+        let revcomp = self.revcomp();
+        if self <= revcomp {
+            (self, true)
+        } else {
+            (revcomp, false)
+        }
+    }
+}
+
+impl<const K: usize> From<Node<K>> for usize {
+    fn from(node: Node<K>) -> usize {
+        node.bs
+    }
+}
+
+impl<const K: usize> TryFrom<usize> for Node<K> {
+    type Error = Error;
+
+    fn try_from(bs: usize) -> Result<Self> {
+        if bs <= Self::mask() {
+            Ok(Node { bs })
+        } else {
+            Err(Error::OutOfBounds {
+                index: bs,
+                bound: Self::mask() + 1,
+            })
+        }
+    }
+}
+
+impl<const K: usize> TryFrom<&SeqSlice<Dna>> for Node<K> {
+    type Error = Error;
+
+    fn try_from(seq: &SeqSlice<Dna>) -> Result<Self> {
+        if seq.len() != K - 1 {
+            return Err(Error::MismatchedLength {
+                expected: K - 1,
+                got: seq.len(),
+            });
+        }
+        let bs = seq.iter().enumerate().fold(0, |bs, (i, base)| {
+            bs | (base as usize) << (i * Dna::BITS as usize)
+        });
+        Ok(Node { bs })
+    }
+}
+
+impl<const K: usize> fmt::Display for Node<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for i in 0..K - 1 {
+            write!(f, "{}", self.nth(i).to_char())?;
+        }
+        Ok(())
+    }
+}
+
+/// A de Bruijn graph with K-mer edges between (K-1)-mer nodes. Edges carry
+/// a multiplicity; an edge with multiplicity zero is absent.
+pub trait Debruijn<const K: usize> {
+    /// Add every K-mer of a sequence as an edge
+    fn add_seq(&mut self, seq: &SeqSlice<Dna>);
+
+    /// Remove an edge whatever its multiplicity
+    fn remove(&mut self, kmer: Kmer<Dna, K>);
+
+    fn multiplicity(&self, kmer: Kmer<Dna, K>) -> usize;
+
+    /// K-mer edges with non-zero multiplicity. Not named `edges` so as not
+    /// to clash with petgraph's `IntoEdges::edges` on graph references.
+    fn kmers(&self) -> impl Iterator<Item = (Kmer<Dna, K>, usize)> + '_;
+
+    /// Edges leaving a node, found by probing the four extensions
+    fn out_edges(&self, node: Node<K>) -> impl Iterator<Item = (Kmer<Dna, K>, usize)> + '_ {
+        BASES.into_iter().filter_map(move |base| {
+            let kmer = node.extend(base);
+            match self.multiplicity(kmer) {
+                0 => None,
+                m => Some((kmer, m)),
+            }
+        })
+    }
+
+    /// Edges entering a node, found by probing the four extensions
+    fn in_edges(&self, node: Node<K>) -> impl Iterator<Item = (Kmer<Dna, K>, usize)> + '_ {
+        BASES.into_iter().filter_map(move |base| {
+            let kmer = node.prepend(base);
+            match self.multiplicity(kmer) {
+                0 => None,
+                m => Some((kmer, m)),
+            }
+        })
+    }
+
+    fn successors(&self, node: Node<K>) -> impl Iterator<Item = Node<K>> + '_ {
+        self.out_edges(node).map(|(kmer, _)| Node::suffix(kmer))
+    }
+
+    fn predecessors(&self, node: Node<K>) -> impl Iterator<Item = Node<K>> + '_ {
+        self.in_edges(node).map(|(kmer, _)| Node::prefix(kmer))
+    }
+
+    /// Sum of the multiplicities of the edges leaving a node
+    fn out_degree(&self, node: Node<K>) -> usize {
+        self.out_edges(node).map(|(_, m)| m).sum()
+    }
+
+    /// Sum of the multiplicities of the edges entering a node
+    fn in_degree(&self, node: Node<K>) -> usize {
+        self.in_edges(node).map(|(_, m)| m).sum()
+    }
+
+    /// Every node incident to an edge, in a stable order
+    fn nodes(&self) -> Vec<Node<K>> {
+        let mut nodes: Vec<Node<K>> = self
+            .kmers()
+            .flat_map(|(kmer, _)| [Node::prefix(kmer), Node::suffix(kmer)])
+            .collect();
+        nodes.sort_unstable();
+        nodes.dedup();
+        nodes
+    }
+
+    /// Whether a walk can traverse every edge as many times as its multiplicity
+    fn eulerian(&self) -> bool
+    where
+        Self: Sized,
+    {
+        eulerian::start(self).is_ok_and(|start| self.walk(start).is_ok())
+    }
+
+    /// Eulerian walk from a start node, see `eulerian::walk`
+    fn walk(&self, start: Node<K>) -> Result<Path<K>>
+    where
+        Self: Sized,
+    {
+        eulerian::walk(self, start)
+    }
+
+    /// Run simplification passes in order, see `clean::clean`
+    fn clean(&mut self, passes: &[Pass]) -> Vec<Removed<K>>
+    where
+        Self: Sized,
+    {
+        clean::clean(self, passes)
+    }
+
+    /// Every path from `start` to `end` as a DAG, see `dag::subdawg`
+    fn subdawg(&self, start: Node<K>, end: Node<K>) -> Result<Dag<K>>
+    where
+        Self: Sized,
+    {
+        dag::subdawg(self, start, end)
+    }
+
+    /// Virtual edges that would balance every node, see `eulerian::balance`
+    fn balance(&self) -> Vec<VirtualEdge<K>>
+    where
+        Self: Sized,
+    {
+        eulerian::balance(self)
+    }
+
+    /// Walks covering every edge once, see `eulerian::contigs`
+    fn contigs(&self) -> Vec<Path<K>>
+    where
+        Self: Sized,
+    {
+        eulerian::contigs(self)
+    }
+
+    /// Natural log of the number of Eulerian circuits, see
+    /// `eulerian::ln_circuits`
+    fn ln_circuits(&self) -> Result<f64>
+    where
+        Self: Sized,
+    {
+        eulerian::ln_circuits(self)
+    }
+
+    /// Compact maximal non-branching paths into unitigs, see `graph::compress`
+    fn compress(&self) -> Unitigs<K>
+    where
+        Self: Sized,
+    {
+        graph::compress(self)
+    }
+
+    /// Shared and unique edges, nodes and unitigs of two graphs, see
+    /// `compare::compare`
+    fn compare<H: Debruijn<K>>(&self, other: &H) -> Comparison<K>
+    where
+        Self: Sized,
+    {
+        compare::compare(self, other)
+    }
+
+    /// Sparse adjacency matrix and its node order, see `matrix::adjacency`
+    fn adjacency(&self) -> (Vec<Node<K>>, Coo)
+    where
+        Self: Sized,
+    {
+        matrix::adjacency(self)
+    }
+
+    /// Sparse directed Laplacian and its node order, see `matrix::laplacian`
+    fn laplacian(&self) -> (Vec<Node<K>>, Coo)
+    where
+        Self: Sized,
+    {
+        matrix::laplacian(self)
+    }
+
+    /// Uncoloured DOT source of the graph, see `dot::debruijn`
+    fn to_dot(&self) -> String
+    where
+        Self: Sized,
+    {
+        dot::debruijn(self, |_| Vec::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_node_edges() {
+        let kmer: Kmer<Dna, 5> = Kmer::try_from(dna!("ACGTG")).unwrap();
+        let prefix = Node::prefix(kmer);
+        let suffix = Node::suffix(kmer);
+
+        assert_eq!(prefix.to_string(), "ACGT");
+        assert_eq!(suffix.to_string(), "CGTG");
+        assert_eq!(prefix.extend(Dna::G), kmer);
+        assert_eq!(suffix.prepend(Dna::A), kmer);
+        assert_eq!(suffix.last(), Dna::G);
+        assert_eq!(prefix.to_seq(), dna!("ACGT"));
+        assert_eq!(prefix.revcomp(), prefix);
+        assert_eq!(suffix.revcomp().to_string(), "CACG");
+        assert_eq!(suffix.revcomp().canonical().0, suffix.canonical().0);
+        assert_eq!(Node::<5>::try_from(dna!("ACGT")).unwrap(), prefix);
+        assert_eq!(
+            Node::<5>::try_from(dna!("ACGTG")),
+            Err(Error::MismatchedLength {
+                expected: 4,
+                got: 5
+            })
+        );
+    }
+}
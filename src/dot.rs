@@ -0,0 +1,267 @@
+use std::fmt::Display;
+use std::io::{self, Write};
+
+use bio_seq::prelude::*;
+
+use crate::{Debruijn, HashGraph, Node};
+
+/// Graphviz colours assigned to colour indices, cycled when there are more
+/// colours than entries
+const PALETTE: [&str; 8] = [
+    "red", "blue", "green3", "orange", "purple", "cyan3", "magenta", "gold",
+];
+
+/// A Graphviz colour list such as `red:blue`, which draws parallel edges and
+/// wedged nodes with a stripe per colour
+fn colour_list(colours: &[usize]) -> String {
+    let names: Vec<&str> = colours
+        .iter()
+        .map(|&c| PALETTE[c % PALETTE.len()])
+        .collect();
+    names.join(":")
+}
+
+/// Line width growing with the log of the multiplicity so that repeats
+/// stand out without swamping the rest of the graph
+fn penwidth(multiplicity: usize) -> f64 {
+    1.0 + (multiplicity.max(1) as f64).log2()
+}
+
+/// A DOT identifier in double quotes with any `"` and `\` escaped, since
+/// node names can come from GFA segment names
+fn quoted(id: impl Display) -> String {
+    let id = id.to_string();
+    let mut out = String::with_capacity(id.len() + 2);
+    out.push('"');
+    for c in id.chars() {
+        if c == '"' || c == '\\' {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out.push('"');
+    out
+}
+
+fn sorted_union(sets: impl Iterator<Item = Vec<usize>>) -> Vec<usize> {
+    let mut union: Vec<usize> = sets.flatten().collect();
+    union.sort_unstable();
+    union.dedup();
+    union
+}
+
+/// Write a de Bruijn graph in DOT format. Edges are labelled with the base
+/// they append and drawn wider the higher their multiplicity. `colours`
+/// gives the colour indices of each edge; nodes take the colours of their
+/// incident edges and uncoloured elements are drawn in the default style.
+pub fn write_debruijn<G, F, W, const K: usize>(graph: &G, out: &mut W, colours: F) -> io::Result<()>
+where
+    G: Debruijn<K>,
+    F: Fn(Kmer<Dna, K>) -> Vec<usize>,
+    W: Write,
+{
+    writeln!(out, "digraph G {{")?;
+
+    for node in graph.nodes() {
+        let incident = graph.out_edges(node).chain(graph.in_edges(node));
+        let node_colours = sorted_union(incident.map(|(kmer, _)| colours(kmer)));
+        if node_colours.is_empty() {
+            writeln!(out, "  {};", quoted(node))?;
+        } else {
+            writeln!(
+                out,
+                "  {} [style=wedged, fillcolor=\"{}\"];",
+                quoted(node),
+                colour_list(&node_colours)
+            )?;
+        }
+    }
+
+    let mut edges: Vec<(Kmer<Dna, K>, usize)> = graph.kmers().collect();
+    edges.sort_unstable();
+
+    for (kmer, multiplicity) in edges {
+        let suffix = Node::suffix(kmer);
+        write!(
+            out,
+            "  {} -> {} [label=\"{}\", penwidth={:.2}",
+            quoted(Node::prefix(kmer)),
+            quoted(suffix),
+            suffix.last().to_char(),
+            penwidth(multiplicity)
+        )?;
+        let edge_colours = colours(kmer);
+        if !edge_colours.is_empty() {
+            write!(out, ", color=\"{}\"", colour_list(&edge_colours))?;
+        }
+        writeln!(out, "];")?;
+    }
+
+    writeln!(out, "}}")
+}
+
+/// DOT source of a de Bruijn graph, see `write_debruijn`
+pub fn debruijn<G, F, const K: usize>(graph: &G, colours: F) -> String
+where
+    G: Debruijn<K>,
+    F: Fn(Kmer<Dna, K>) -> Vec<usize>,
+{
+    let mut out: Vec<u8> = Vec::new();
+    write_debruijn(graph, &mut out, colours).expect("writing to a Vec does not fail");
+    String::from_utf8(out).expect("DOT output is ASCII")
+}
+
+impl HashGraph {
+    /// Write the graph in DOT format with a node per sequence. Links are
+    /// labelled with the first base the target appends past the overlap and
+    /// nodes with a known k-mer count are drawn with a wider border. Nodes
+    /// are filled by their colours and links by the colours shared by both
+    /// ends.
+    pub fn write_dot<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "digraph G {{")?;
+
+        for (node, seq) in self.index.iter().enumerate() {
+            write!(out, "  {} [label=\"{seq}\"", quoted(self.name(node)))?;
+            if let Some(&count) = self.counts.get(&node) {
+                write!(out, ", penwidth={:.2}", penwidth(count))?;
+            }
+            if let Some(colours) = self.colours.get(&node)
+                && !colours.is_empty()
+            {
+                write!(
+                    out,
+                    ", style=wedged, fillcolor=\"{}\"",
+                    colour_list(colours)
+                )?;
+            }
+            writeln!(out, "];")?;
+        }
+
+        for link in self.all_links() {
+            let to = self.oriented(link.to, link.to_forward);
+            write!(
+                out,
+                "  {} -> {} [label=\"{}\"",
+                quoted(self.name(link.from)),
+                quoted(self.name(link.to)),
+                to.get(link.overlap).map_or('*', |base| base.to_char())
+            )?;
+            if !(link.from_forward && link.to_forward) {
+                // reverse ends are drawn with inverted arrows
+                write!(
+                    out,
+                    ", dir=both, arrowtail={}, arrowhead={}",
+                    if link.from_forward { "none" } else { "inv" },
+                    if link.to_forward { "normal" } else { "inv" }
+                )?;
+            }
+            let shared: Vec<usize> =
+                match (self.colours.get(&link.from), self.colours.get(&link.to)) {
+                    (Some(from), Some(to)) => {
+                        from.iter().filter(|c| to.contains(c)).copied().collect()
+                    }
+                    _ => Vec::new(),
+                };
+            if !shared.is_empty() {
+                write!(out, ", color=\"{}\"", colour_list(&shared))?;
+            }
+            writeln!(out, "];")?;
+        }
+
+        writeln!(out, "}}")
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut out: Vec<u8> = Vec::new();
+        self.write_dot(&mut out)
+            .expect("writing to a Vec does not fail");
+        String::from_utf8(out).expect("DOT output is ASCII")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{KmerTable, Link};
+
+    #[test]
+    fn test_debruijn_dot() {
+        let mut graph: KmerTable<u32, 4> = KmerTable::default();
+        graph.add_seq(dna!("ACGTT"));
+        graph.add_seq(dna!("ACGTA"));
+        graph.add_seq(dna!("ACGT"));
+
+        let dot = graph.to_dot();
+        let lines: Vec<&str> = dot.lines().collect();
+        assert_eq!(lines.first(), Some(&"digraph G {"));
+        assert_eq!(lines.last(), Some(&"}"));
+        assert!(lines.contains(&"  \"ACG\" -> \"CGT\" [label=\"T\", penwidth=2.58];"));
+        assert!(lines.contains(&"  \"CGT\" -> \"GTT\" [label=\"T\", penwidth=1.00];"));
+        assert!(lines.contains(&"  \"GTA\";"));
+
+        // colour the edges by whether they end in T
+        let dot = debruijn(&graph, |kmer: Kmer<Dna, 4>| {
+            if Node::suffix(kmer).last() == Dna::T {
+                vec![0, 1]
+            } else {
+                vec![1]
+            }
+        });
+        let lines: Vec<&str> = dot.lines().collect();
+        assert!(
+            lines.contains(&"  \"CGT\" -> \"GTA\" [label=\"A\", penwidth=1.00, color=\"blue\"];")
+        );
+        assert!(lines.contains(&"  \"CGT\" [style=wedged, fillcolor=\"red:blue\"];"));
+        assert!(lines.contains(&"  \"GTA\" [style=wedged, fillcolor=\"blue\"];"));
+    }
+
+    #[test]
+    fn test_hash_graph_dot() {
+        let mut graph = HashGraph::default();
+        let a = graph.add(dna!("ACGTT"));
+        let b = graph.add(dna!("GTTCA"));
+        let c = graph.add(dna!("TAAAC"));
+        graph.link(Link {
+            from: a,
+            from_forward: true,
+            to: b,
+            to_forward: true,
+            overlap: 3,
+        });
+        graph.link(Link {
+            from: a,
+            from_forward: true,
+            to: c,
+            to_forward: false,
+            overlap: 3,
+        });
+        graph.counts.insert(a, 4);
+        graph.colours.insert(a, vec![0, 1]);
+        graph.colours.insert(b, vec![1]);
+
+        assert_eq!(
+            graph.to_dot(),
+            "digraph G {\n\
+             \x20 \"1\" [label=\"ACGTT\", penwidth=3.00, style=wedged, fillcolor=\"red:blue\"];\n\
+             \x20 \"2\" [label=\"GTTCA\", style=wedged, fillcolor=\"blue\"];\n\
+             \x20 \"3\" [label=\"TAAAC\"];\n\
+             \x20 \"1\" -> \"2\" [label=\"C\", color=\"blue\"];\n\
+             \x20 \"1\" -> \"3\" [label=\"T\", dir=both, arrowtail=none, arrowhead=inv];\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn test_dot_escapes_names() {
+        let graph = HashGraph::from_gfa(
+            "S\tcontig \"a\"\tACGTT\nS\tdir\\b\tGTTCA\nL\tcontig \"a\"\t+\tdir\\b\t+\t3M\n",
+        )
+        .unwrap();
+
+        let dot = graph.to_dot();
+        let lines: Vec<&str> = dot.lines().collect();
+        assert!(lines.contains(&"  \"contig \\\"a\\\"\" [label=\"ACGTT\"];"));
+        assert!(lines.contains(&"  \"dir\\\\b\" [label=\"GTTCA\"];"));
+        assert!(lines.contains(&"  \"contig \\\"a\\\"\" -> \"dir\\\\b\" [label=\"C\"];"));
+    }
+}
@@ -0,0 +1,78 @@
+use core::fmt;
+
+use bio_seq::prelude::ParseBioError;
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    /// A sequence or k-mer did not have the expected length
+    MismatchedLength {
+        expected: usize,
+        got: usize,
+    },
+    /// A sequence is too long to be addressed by the index
+    SequenceTooLong {
+        max: usize,
+        got: usize,
+    },
+    /// Alignment coordinates that do not describe a valid interval
+    InvalidCoordinates {
+        start: u32,
+        end: u32,
+    },
+    /// An indel length that does not fit in an `i32`
+    IndelOverflow(i64),
+    /// An index outside the bounds of a container
+    OutOfBounds {
+        index: usize,
+        bound: usize,
+    },
+    /// A graph has no Eulerian path from the requested node
+    NotEulerian(String),
+    /// There is no path between two nodes of a graph
+    NoPath(String),
+    /// A malformed GFA record, with its one-based line number
+    Gfa {
+        line: usize,
+        reason: String,
+    },
+    Parse(ParseBioError),
+}
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::MismatchedLength { expected, got } => {
+                write!(f, "Expected length {expected}, got {got}")
+            }
+            Error::SequenceTooLong { max, got } => {
+                write!(f, "Expected length <= {max}, got {got}")
+            }
+            Error::InvalidCoordinates { start, end } => {
+                write!(f, "Invalid interval: start {start} is after end {end}")
+            }
+            Error::IndelOverflow(indel) => write!(f, "Indel of {indel} bases overflows i32"),
+            Error::OutOfBounds { index, bound } => {
+                write!(f, "Index {index} out of bounds ({bound})")
+            }
+            Error::NotEulerian(reason) => write!(f, "No Eulerian path: {reason}"),
+            Error::NoPath(reason) => write!(f, "No path: {reason}"),
+            Error::Gfa { line, reason } => write!(f, "GFA line {line}: {reason}"),
+            Error::Parse(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl From<ParseBioError> for Error {
+    fn from(err: ParseBioError) -> Self {
+        match err {
+            ParseBioError::MismatchedLength(expected, got) => {
+                Error::MismatchedLength { expected, got }
+            }
+            err => Error::Parse(err),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,566 @@
+use core::fmt;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use bio_seq::prelude::*;
+
+use crate::debruijn::BASES;
+use crate::error::{Error, Result};
+use crate::{Debruijn, Node};
+
+/// A walk through a de Bruijn graph as the sequence of visited nodes
+#[derive(Clone, Debug, PartialEq)]
+pub struct Path<const K: usize> {
+    pub nodes: Vec<Node<K>>,
+}
+
+impl<const K: usize> Path<K> {
+    /// The K-mer edges traversed by the walk
+    pub fn edges(&self) -> impl Iterator<Item = Kmer<Dna, K>> + '_ {
+        self.nodes
+            .windows(2)
+            .map(|pair| pair[0].extend(pair[1].last()))
+    }
+
+    /// The sequence spelled by the walk: the first node followed by the
+    /// last base of every subsequent node
+    pub fn to_seq(&self) -> Seq<Dna> {
+        let mut seq: Seq<Dna> = Seq::new();
+        if let Some(first) = self.nodes.first() {
+            seq.append(&first.to_seq());
+        }
+        seq.extend(self.nodes.iter().skip(1).map(|node| node.last()));
+        seq
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+/// The node an Eulerian path must start from: the unique node with one more
+/// outgoing than incoming edge, or any node with an outgoing edge if every
+/// node is balanced.
+pub fn start<G: Debruijn<K>, const K: usize>(graph: &G) -> Result<Node<K>> {
+    let mut start: Option<Node<K>> = None;
+    let mut end: Option<Node<K>> = None;
+
+    for node in graph.nodes() {
+        let out_degree = graph.out_degree(node) as i64;
+        let in_degree = graph.in_degree(node) as i64;
+
+        match out_degree - in_degree {
+            0 => (),
+            1 if start.is_none() => start = Some(node),
+            -1 if end.is_none() => end = Some(node),
+            _ => {
+                return Err(Error::NotEulerian(format!(
+                    "node {node} has in-degree {in_degree} and out-degree {out_degree}"
+                )));
+            }
+        }
+    }
+
+    match (start, end) {
+        (Some(start), Some(_)) => Ok(start),
+        (None, None) => graph
+            .kmers()
+            .next()
+            .map(|(kmer, _)| Node::prefix(kmer))
+            .ok_or(Error::NotEulerian("graph has no edges".to_string())),
+        _ => Err(Error::NotEulerian(
+            "graph has an unmatched unbalanced node".to_string(),
+        )),
+    }
+}
+
+/// Iterative Hierholzer walk that traverses every edge as many times as its
+/// multiplicity
+pub fn walk<G: Debruijn<K>, const K: usize>(graph: &G, from: Node<K>) -> Result<Path<K>> {
+    let expected = start(graph)?;
+    if graph.out_degree(expected) != graph.in_degree(expected) && from != expected {
+        return Err(Error::NotEulerian(format!(
+            "an Eulerian path must start from {expected}, not {from}"
+        )));
+    }
+    if graph.out_degree(from) == 0 {
+        return Err(Error::NotEulerian(format!(
+            "start node {from} has no outgoing edges"
+        )));
+    }
+
+    let mut remaining: HashMap<Kmer<Dna, K>, usize> = graph.kmers().collect();
+    let total: usize = remaining.values().sum();
+
+    let mut stack: Vec<Node<K>> = vec![from];
+    let mut nodes: Vec<Node<K>> = Vec::with_capacity(total + 1);
+
+    while let Some(&node) = stack.last() {
+        let next = BASES.into_iter().find_map(|base| {
+            let kmer = node.extend(base);
+            match remaining.get_mut(&kmer) {
+                Some(count) if *count > 0 => {
+                    *count -= 1;
+                    Some(Node::suffix(kmer))
+                }
+                _ => None,
+            }
+        });
+
+        match next {
+            Some(next) => stack.push(next),
+            None => {
+                stack.pop();
+                nodes.push(node);
+            }
+        }
+    }
+    nodes.reverse();
+
+    if nodes.len() != total + 1 {
+        return Err(Error::NotEulerian(format!(
+            "graph is disconnected: walk traversed {} of {total} edges",
+            nodes.len() - 1
+        )));
+    }
+    Ok(Path { nodes })
+}
+
+/// An edge added to balance a graph. It stands for the pair of edges
+/// `from -> $ -> to` through a sentinel node, and joins the end of one
+/// linear contig to the start of another.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VirtualEdge<const K: usize> {
+    pub from: Node<K>,
+    pub to: Node<K>,
+    pub multiplicity: usize,
+}
+
+impl<const K: usize> fmt::Display for VirtualEdge<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} -> $ -> {}", self.from, self.to)
+    }
+}
+
+/// The fewest virtual edges that balance every node: each node with more
+/// incoming than outgoing edges is joined to nodes with more outgoing than
+/// incoming edges, in node order
+pub fn balance<G: Debruijn<K>, const K: usize>(graph: &G) -> Vec<VirtualEdge<K>> {
+    let mut ends: Vec<(Node<K>, usize)> = Vec::new();
+    let mut starts: Vec<(Node<K>, usize)> = Vec::new();
+    for node in graph.nodes() {
+        let (out_degree, in_degree) = (graph.out_degree(node), graph.in_degree(node));
+        if in_degree > out_degree {
+            ends.push((node, in_degree - out_degree));
+        } else if out_degree > in_degree {
+            starts.push((node, out_degree - in_degree));
+        }
+    }
+
+    let mut edges: Vec<VirtualEdge<K>> = Vec::new();
+    let mut starts = starts.into_iter();
+    let mut start = starts.next();
+    for (from, mut surplus) in ends {
+        while surplus > 0 {
+            let Some((to, needed)) = start.as_mut() else {
+                unreachable!("a graph has as many edge ends as edge starts");
+            };
+            let multiplicity = surplus.min(*needed);
+            edges.push(VirtualEdge {
+                from,
+                to: *to,
+                multiplicity,
+            });
+            surplus -= multiplicity;
+            *needed -= multiplicity;
+            if *needed == 0 {
+                start = starts.next();
+            }
+        }
+    }
+    edges
+}
+
+/// Cover every edge with walks: balance the graph, take an Eulerian
+/// circuit of each connected component and split the circuits at the
+/// virtual edges. Components without virtual edges are returned as closed
+/// walks that start and end on the same node.
+pub fn contigs<G: Debruijn<K>, const K: usize>(graph: &G) -> Vec<Path<K>> {
+    let mut remaining: HashMap<Kmer<Dna, K>, usize> = graph.kmers().collect();
+    let mut virtual_edges: HashMap<Node<K>, Vec<(Node<K>, usize)>> = HashMap::new();
+    for edge in balance(graph) {
+        virtual_edges
+            .entry(edge.from)
+            .or_default()
+            .push((edge.to, edge.multiplicity));
+    }
+
+    let mut paths: Vec<Path<K>> = Vec::new();
+    for from in graph.nodes() {
+        if graph.out_edges(from).all(|(kmer, _)| remaining[&kmer] == 0) {
+            continue;
+        }
+        // nodes of the circuit, each with whether it was entered by a
+        // virtual edge
+        let mut stack: Vec<(Node<K>, bool)> = vec![(from, false)];
+        let mut circuit: Vec<(Node<K>, bool)> = Vec::new();
+
+        while let Some(&(node, _)) = stack.last() {
+            let next = BASES
+                .into_iter()
+                .find_map(|base| {
+                    let kmer = node.extend(base);
+                    match remaining.get_mut(&kmer) {
+                        Some(count) if *count > 0 => {
+                            *count -= 1;
+                            Some((Node::suffix(kmer), false))
+                        }
+                        _ => None,
+                    }
+                })
+                .or_else(|| {
+                    let edges = virtual_edges.get_mut(&node)?;
+                    let (to, count) = edges.iter_mut().find(|(_, count)| *count > 0)?;
+                    *count -= 1;
+                    Some((*to, true))
+                });
+
+            match next {
+                Some(next) => stack.push(next),
+                None => circuit.extend(stack.pop()),
+            }
+        }
+        circuit.reverse();
+        paths.extend(split_circuit(&circuit));
+    }
+    paths
+}
+
+/// Split a closed walk at its virtual edges, starting after the first one
+fn split_circuit<const K: usize>(circuit: &[(Node<K>, bool)]) -> Vec<Path<K>> {
+    let Some(first) = circuit.iter().position(|&(_, virtual_edge)| virtual_edge) else {
+        let nodes = circuit.iter().map(|&(node, _)| node).collect();
+        return vec![Path { nodes }];
+    };
+
+    // the first node repeats as the last, so skip it when wrapping around
+    let edges = circuit.len() - 1;
+    let mut paths: Vec<Path<K>> = Vec::new();
+    let mut nodes: Vec<Node<K>> = vec![circuit[first].0];
+    for i in 1..=edges {
+        let (node, virtual_edge) = circuit[(first + i - 1) % edges + 1];
+        if virtual_edge {
+            paths.push(Path {
+                nodes: std::mem::take(&mut nodes),
+            });
+        }
+        nodes.push(node);
+    }
+    paths
+}
+
+/// Natural log of the number of Eulerian circuits by the BEST theorem:
+/// the number of arborescences rooted at any node times the product of
+/// `(d+(v) - 1)!`. Parallel copies of an edge are counted as distinct edges.
+/// The arborescences are counted with a sparse log-determinant of the
+/// reduced Laplacian, so large graphs neither overflow nor need a dense
+/// matrix.
+pub fn ln_circuits<G: Debruijn<K>, const K: usize>(graph: &G) -> Result<f64> {
+    let nodes = graph.nodes();
+    if nodes.is_empty() {
+        return Err(Error::NotEulerian("graph has no edges".to_string()));
+    }
+    for &node in &nodes {
+        if graph.out_degree(node) != graph.in_degree(node) {
+            return Err(Error::NotEulerian(format!("node {node} is unbalanced")));
+        }
+    }
+    let ids: HashMap<Node<K>, usize> = nodes.iter().enumerate().map(|(i, &n)| (n, i)).collect();
+
+    // Laplacian D_out - A without the row and column of the first node
+    let n = nodes.len() - 1;
+    let mut laplacian: Vec<HashMap<usize, f64>> = vec![HashMap::new(); n];
+    let mut ln_factorials = 0.0;
+
+    for (i, &node) in nodes.iter().enumerate() {
+        let degree = graph.out_degree(node);
+        ln_factorials += (2..degree).map(|d| (d as f64).ln()).sum::<f64>();
+        if i == 0 {
+            continue;
+        }
+        let row = &mut laplacian[i - 1];
+        *row.entry(i - 1).or_default() += degree as f64;
+        for (kmer, multiplicity) in graph.out_edges(node) {
+            let j = ids[&Node::suffix(kmer)];
+            if j > 0 {
+                *row.entry(j - 1).or_default() -= multiplicity as f64;
+            }
+        }
+    }
+
+    let ln_arborescences =
+        ln_det(laplacian).ok_or(Error::NotEulerian("graph is disconnected".to_string()))?;
+    Ok(ln_arborescences + ln_factorials)
+}
+
+/// Log-determinant of a sparse matrix given by rows, or `None` if it is
+/// singular. The reduced Laplacian of a balanced graph is diagonally
+/// dominant by rows and columns, so elimination on the diagonal is stable
+/// without numerical pivoting and the pivots can be ordered freely: the
+/// node with the fewest non-zeros goes first to limit fill-in.
+fn ln_det(mut rows: Vec<HashMap<usize, f64>>) -> Option<f64> {
+    let n = rows.len();
+    let mut cols: Vec<HashSet<usize>> = vec![HashSet::new(); n];
+    for (i, row) in rows.iter().enumerate() {
+        for &j in row.keys() {
+            cols[j].insert(i);
+        }
+    }
+    // tolerance relative to the largest diagonal for cancelled pivots
+    let scale = (0..n)
+        .map(|i| rows[i].get(&i).copied().unwrap_or(0.0).abs())
+        .fold(1.0, f64::max);
+
+    let degree = |i: usize, rows: &[HashMap<usize, f64>], cols: &[HashSet<usize>]| {
+        rows[i].len() + cols[i].len()
+    };
+    let mut queue: BinaryHeap<Reverse<(usize, usize)>> = (0..n)
+        .map(|i| Reverse((degree(i, &rows, &cols), i)))
+        .collect();
+    let mut eliminated = vec![false; n];
+    let mut ln_det = 0.0;
+
+    while let Some(Reverse((d, p))) = queue.pop() {
+        if eliminated[p] || d != degree(p, &rows, &cols) {
+            // stale entry, the node was re-queued with its current degree
+            continue;
+        }
+        eliminated[p] = true;
+
+        let pivot = rows[p].remove(&p).unwrap_or(0.0);
+        if pivot <= 1e-9 * scale {
+            return None;
+        }
+        ln_det += pivot.ln();
+        cols[p].remove(&p);
+
+        let pivot_row: Vec<(usize, f64)> = rows[p].drain().collect();
+        for &(j, _) in &pivot_row {
+            cols[j].remove(&p);
+        }
+        let pivot_col: Vec<usize> = cols[p].drain().collect();
+
+        for i in pivot_col {
+            let factor = rows[i].remove(&p).unwrap_or(0.0) / pivot;
+            for &(j, value) in &pivot_row {
+                *rows[i].entry(j).or_default() -= factor * value;
+                cols[j].insert(i);
+            }
+            queue.push(Reverse((degree(i, &rows, &cols), i)));
+        }
+        for &(j, _) in &pivot_row {
+            queue.push(Reverse((degree(j, &rows, &cols), j)));
+        }
+    }
+    Some(ln_det)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{KmerArray, KmerTable};
+
+    #[test]
+    fn test_walk_linear() {
+        let seq = dna!("ACGAAACTTGTTGGCCCAGTGTGAATCGCTTA");
+        let mut graph: KmerTable<u32, 6> = KmerTable::default();
+        graph.add_seq(seq);
+
+        let path = graph.walk(Node::try_from(&seq[..5]).unwrap()).unwrap();
+        assert_eq!(path.to_seq(), seq);
+        assert_eq!(path.edges().count(), seq.len() - 5);
+        assert!(graph.eulerian());
+    }
+
+    #[test]
+    fn test_walk_multiplicity() {
+        // every edge is traversed twice
+        let seq = dna!("ACGACGACG");
+        let mut graph: KmerArray<u8, 4> = KmerArray::default();
+        graph.add_seq(seq);
+
+        let path = graph.walk(Node::try_from(dna!("ACG")).unwrap()).unwrap();
+        assert_eq!(path.to_seq(), seq);
+    }
+
+    #[test]
+    fn test_walk_circuit() {
+        // ACGTT -> CGTTA -> GTTAC -> TTACG -> TACGT -> ACGTT
+        let mut graph: KmerTable<u32, 5> = KmerTable::default();
+        graph.add_seq(dna!("ACGTTACGT"));
+
+        let from = Node::try_from(dna!("TTAC")).unwrap();
+        let path = graph.walk(from).unwrap();
+        assert_eq!(path.nodes.first(), path.nodes.last());
+        assert_eq!(path.to_seq(), dna!("TTACGTTAC"));
+    }
+
+    #[test]
+    fn test_not_eulerian() {
+        let mut graph: KmerTable<u32, 4> = KmerTable::default();
+        graph.add_seq(dna!("ACGTTT"));
+        graph.add_seq(dna!("GGGCAC"));
+        assert!(!graph.eulerian());
+        assert!(matches!(
+            graph.walk(Node::try_from(dna!("ACG")).unwrap()),
+            Err(Error::NotEulerian(_))
+        ));
+
+        let mut graph: KmerTable<u32, 4> = KmerTable::default();
+        graph.add_seq(dna!("ACGTTT"));
+        assert!(matches!(
+            graph.walk(Node::try_from(dna!("CGT")).unwrap()),
+            Err(Error::NotEulerian(_))
+        ));
+    }
+
+    /// Count circuits by trying every order of the edges that starts with
+    /// the first edge, then label the parallel copies of each edge
+    fn brute_force_circuits<G: Debruijn<K>, const K: usize>(graph: &G) -> f64 {
+        fn extend<G: Debruijn<K>, const K: usize>(
+            graph: &G,
+            node: Node<K>,
+            end: Node<K>,
+            remaining: &mut HashMap<Kmer<Dna, K>, usize>,
+            left: usize,
+        ) -> usize {
+            if left == 0 {
+                return (node == end) as usize;
+            }
+            let mut count = 0;
+            for (kmer, _) in graph.out_edges(node) {
+                if remaining[&kmer] > 0 {
+                    *remaining.get_mut(&kmer).unwrap() -= 1;
+                    count += extend(graph, Node::suffix(kmer), end, remaining, left - 1);
+                    *remaining.get_mut(&kmer).unwrap() += 1;
+                }
+            }
+            count
+        }
+
+        let mut remaining: HashMap<Kmer<Dna, K>, usize> = graph.kmers().collect();
+        let mut edges: Vec<_> = remaining.iter().map(|(&k, &m)| (k, m)).collect();
+        edges.sort_unstable();
+        let (first, m) = edges[0];
+        let total: usize = remaining.values().sum();
+        *remaining.get_mut(&first).unwrap() -= 1;
+
+        let orders = extend(
+            graph,
+            Node::suffix(first),
+            Node::prefix(first),
+            &mut remaining,
+            total - 1,
+        );
+        let factorial = |m: usize| (1..=m).product::<usize>() as f64;
+        let labellings: f64 = edges.iter().map(|&(_, m)| factorial(m)).product();
+        orders as f64 * labellings / m as f64
+    }
+
+    #[test]
+    fn test_ln_circuits() {
+        let mut cycle: KmerTable<u32, 5> = KmerTable::default();
+        cycle.add_seq(dna!("ACGTTACGT"));
+        assert!(ln_circuits(&cycle).unwrap().abs() < 1e-9);
+
+        // every edge twice: the three non-root nodes each pick one of two
+        // copies of their out-edge for the arborescence
+        let mut double: KmerArray<u8, 4> = KmerArray::default();
+        double.add_seq(dna!("ACGACGACG"));
+        assert!((ln_circuits(&double).unwrap() - 4f64.ln()).abs() < 1e-9);
+
+        for seq in [
+            dna!("ACGTACGGTACTTACGTACG"),
+            dna!("GATTACAGATCAGATTAGATCAGAT"),
+            dna!("AAAAACAAAATAAAAGAAAA"),
+        ] {
+            let mut graph: KmerTable<u32, 4> = KmerTable::default();
+            graph.add_seq(seq);
+            let expected = brute_force_circuits(&graph).ln();
+            assert!((ln_circuits(&graph).unwrap() - expected).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_ln_circuits_not_eulerian() {
+        let mut graph: KmerTable<u32, 4> = KmerTable::default();
+        graph.add_seq(dna!("ACGTTT"));
+        assert!(matches!(ln_circuits(&graph), Err(Error::NotEulerian(_))));
+
+        // two disjoint cycles
+        graph = KmerTable::default();
+        graph.add_seq(dna!("ACGTTACGT"));
+        graph.add_seq(dna!("GGGCCGGG"));
+        assert!(matches!(ln_circuits(&graph), Err(Error::NotEulerian(_))));
+    }
+
+    #[test]
+    fn test_balance() {
+        let mut graph: KmerTable<u32, 4> = KmerTable::default();
+        graph.add_seq(dna!("ACGTTT"));
+        graph.add_seq(dna!("ACGTAA"));
+        graph.add_seq(dna!("CCCTAA"));
+
+        let edges = balance(&graph);
+        let ends: Vec<String> = edges.iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            ends,
+            vec!["TAA -> $ -> CCC", "TAA -> $ -> ACG", "TTT -> $ -> ACG"]
+        );
+
+        for node in graph.nodes() {
+            let added_out: usize = edges
+                .iter()
+                .filter(|e| e.from == node)
+                .map(|e| e.multiplicity)
+                .sum();
+            let added_in: usize = edges
+                .iter()
+                .filter(|e| e.to == node)
+                .map(|e| e.multiplicity)
+                .sum();
+            assert_eq!(
+                graph.out_degree(node) + added_out,
+                graph.in_degree(node) + added_in
+            );
+        }
+    }
+
+    #[test]
+    fn test_contigs() {
+        let mut graph: KmerTable<u32, 5> = KmerTable::default();
+        graph.add_seq(dna!("ACGAAACTTGTTGG"));
+        graph.add_seq(dna!("GGGCCCAGTG"));
+        graph.add_seq(dna!("ACGTTACGT"));
+
+        let mut seqs: Vec<String> = contigs(&graph)
+            .iter()
+            .map(|path| path.to_seq().to_string())
+            .collect();
+        seqs.sort();
+        // the cycle is returned as a closed walk from its first node
+        assert_eq!(seqs, vec!["ACGAAACTTGTTGG", "GGGCCCAGTG", "GTTACGTTA"]);
+
+        // a branch splits the circuit into two linear contigs
+        let mut graph: KmerTable<u32, 4> = KmerTable::default();
+        graph.add_seq(dna!("ACGTTT"));
+        graph.add_seq(dna!("ACGTAA"));
+        let paths = contigs(&graph);
+        assert_eq!(paths.iter().map(|p| p.edges().count()).sum::<usize>(), 6);
+        assert_eq!(paths.len(), 2);
+    }
+}
@@ -0,0 +1,399 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+use bio_seq::prelude::*;
+
+use crate::error::{Error, Result};
+use crate::{HashGraph, Link};
+
+/// Optional tags written on GFA segment lines
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GfaOptions {
+    /// `KC:i:` total k-mer count of the segment
+    pub kmer_counts: bool,
+    /// `LN:i:` segment length
+    pub lengths: bool,
+    /// `SN:Z:` comma separated names of the samples the segment occurs in.
+    /// Viewers such as Bandage read `CL:Z:` as a display colour, so the
+    /// samples get a tag of their own.
+    pub colours: bool,
+}
+
+fn orientation(forward: bool) -> char {
+    if forward { '+' } else { '-' }
+}
+
+impl HashGraph {
+    /// Segment name of a node. Unnamed nodes are numbered from one since
+    /// GFA consumers like vg reserve 0.
+    pub fn name(&self, node: usize) -> String {
+        match self.names.get(&node) {
+            Some(name) => name.clone(),
+            None => (node + 1).to_string(),
+        }
+    }
+
+    /// Write the graph as GFA 1.0 with a segment per node and a link per
+    /// oriented edge
+    pub fn write_gfa<W: Write>(&self, out: &mut W, options: &GfaOptions) -> io::Result<()> {
+        writeln!(out, "H\tVN:Z:1.0")?;
+
+        for (node, seq) in self.index.iter().enumerate() {
+            write!(out, "S\t{}\t{}", self.name(node), seq)?;
+            if options.lengths {
+                write!(out, "\tLN:i:{}", seq.len())?;
+            }
+            if let Some(count) = self.counts.get(&node)
+                && options.kmer_counts
+            {
+                write!(out, "\tKC:i:{count}")?;
+            }
+            if let Some(colours) = self.colours.get(&node)
+                && options.colours
+            {
+                let names: Vec<&str> = colours
+                    .iter()
+                    .map(|&c| self.samples.get(c).map_or("?", String::as_str))
+                    .collect();
+                write!(out, "\tSN:Z:{}", names.join(","))?;
+            }
+            writeln!(out)?;
+        }
+
+        for link in self.all_links() {
+            writeln!(
+                out,
+                "L\t{}\t{}\t{}\t{}\t{}M",
+                self.name(link.from),
+                orientation(link.from_forward),
+                self.name(link.to),
+                orientation(link.to_forward),
+                link.overlap
+            )?;
+        }
+
+        for (name, steps) in &self.paths {
+            let steps: Vec<String> = steps
+                .iter()
+                .map(|&(node, forward)| format!("{}{}", self.name(node), orientation(forward)))
+                .collect();
+            writeln!(out, "P\t{name}\t{}\t*", steps.join(","))?;
+        }
+        Ok(())
+    }
+
+    pub fn to_gfa(&self, options: &GfaOptions) -> String {
+        let mut out: Vec<u8> = Vec::new();
+        self.write_gfa(&mut out, options)
+            .expect("writing to a Vec does not fail");
+        String::from_utf8(out).expect("GFA output is ASCII")
+    }
+
+    /// Parse a GFA 1.0 graph. Segments with their `KC` counts and `SN`
+    /// samples, links and paths are kept; headers, comments and other record
+    /// types are skipped. Link overlaps must be a single match operation and
+    /// are checked against the segment sequences.
+    pub fn read_gfa<R: BufRead>(input: R) -> Result<Self> {
+        let mut graph = HashGraph::default();
+        let mut ids: HashMap<String, usize> = HashMap::new();
+        // links and paths may refer to segments that are defined later
+        let mut links: Vec<(usize, Vec<String>)> = Vec::new();
+        let mut paths: Vec<(usize, Vec<String>)> = Vec::new();
+
+        for (i, line) in input.lines().enumerate() {
+            let line_no = i + 1;
+            let line = line.map_err(|err| gfa_error(line_no, &err.to_string()))?;
+            let fields: Vec<String> = line.split('\t').map(str::to_string).collect();
+
+            match fields[0].as_str() {
+                "S" => {
+                    if fields.len() < 3 {
+                        return Err(gfa_error(line_no, "segment needs a name and a sequence"));
+                    }
+                    let name = &fields[1];
+                    if ids.contains_key(name) {
+                        return Err(gfa_error(line_no, &format!("duplicate segment {name}")));
+                    }
+                    let seq: Seq<Dna> = match fields[2].as_str() {
+                        "*" => Seq::new(),
+                        seq => segment_seq(line_no, name, seq)?,
+                    };
+                    let node = graph.add(&seq);
+
+                    for tag in &fields[3..] {
+                        if let Some(count) = tag.strip_prefix("KC:i:") {
+                            let count = count
+                                .parse()
+                                .map_err(|_| gfa_error(line_no, &format!("invalid tag {tag}")))?;
+                            graph.counts.insert(node, count);
+                        } else if let Some(names) = tag.strip_prefix("SN:Z:") {
+                            let colours = names.split(',').map(|name| {
+                                match graph.samples.iter().position(|s| s == name) {
+                                    Some(colour) => colour,
+                                    None => {
+                                        graph.samples.push(name.to_string());
+                                        graph.samples.len() - 1
+                                    }
+                                }
+                            });
+                            let colours: Vec<usize> = colours.collect();
+                            graph.colours.insert(node, colours);
+                        } else if let Some(len) = tag.strip_prefix("LN:i:")
+                            && !seq.is_empty()
+                            && len.parse() != Ok(seq.len())
+                        {
+                            return Err(gfa_error(
+                                line_no,
+                                &format!("{tag} does not match sequence length {}", seq.len()),
+                            ));
+                        }
+                    }
+                    graph.names.insert(node, name.clone());
+                    ids.insert(name.clone(), node);
+                }
+                "L" => {
+                    if fields.len() < 6 {
+                        return Err(gfa_error(line_no, "link needs 5 fields"));
+                    }
+                    links.push((line_no, fields));
+                }
+                "P" => {
+                    if fields.len() < 3 {
+                        return Err(gfa_error(line_no, "path needs a name and segments"));
+                    }
+                    paths.push((line_no, fields));
+                }
+                _ => (),
+            }
+        }
+
+        let segment = |line_no: usize, name: &str| {
+            ids.get(name)
+                .copied()
+                .ok_or_else(|| gfa_error(line_no, &format!("unknown segment {name}")))
+        };
+        let forward = |line_no: usize, orientation: &str| match orientation {
+            "+" => Ok(true),
+            "-" => Ok(false),
+            _ => Err(gfa_error(
+                line_no,
+                &format!("invalid orientation {orientation}"),
+            )),
+        };
+
+        for (line_no, fields) in links {
+            let link = Link {
+                from: segment(line_no, &fields[1])?,
+                from_forward: forward(line_no, &fields[2])?,
+                to: segment(line_no, &fields[3])?,
+                to_forward: forward(line_no, &fields[4])?,
+                overlap: overlap(line_no, &fields[5])?,
+            };
+            validate_overlap(&graph, &link).map_err(|reason| gfa_error(line_no, &reason))?;
+            graph.link(link);
+        }
+
+        for (line_no, fields) in paths {
+            let mut steps: Vec<(usize, bool)> = Vec::new();
+            for step in fields[2].split(',') {
+                let (name, orientation) =
+                    step.split_at(step.char_indices().last().map_or(0, |(i, _)| i));
+                steps.push((segment(line_no, name)?, forward(line_no, orientation)?));
+            }
+            graph.paths.push((fields[1].clone(), steps));
+        }
+
+        Ok(graph)
+    }
+
+    pub fn from_gfa(gfa: &str) -> Result<Self> {
+        Self::read_gfa(gfa.as_bytes())
+    }
+}
+
+fn gfa_error(line: usize, reason: &str) -> Error {
+    Error::Gfa {
+        line,
+        reason: reason.to_string(),
+    }
+}
+
+/// Sequence of a segment. N and other IUPAC codes can not be stored in a
+/// `Seq<Dna>`, so the first such base is reported with the segment name.
+fn segment_seq(line_no: usize, name: &str, seq: &str) -> Result<Seq<Dna>> {
+    if let Some((i, base)) = seq
+        .char_indices()
+        .find(|&(_, c)| !c.is_ascii() || Dna::try_from_ascii(c as u8).is_none())
+    {
+        return Err(gfa_error(
+            line_no,
+            &format!(
+                "segment {name} has unsupported base {base} at position {}",
+                i + 1
+            ),
+        ));
+    }
+    Seq::from_str(seq).map_err(|err| gfa_error(line_no, &err.to_string()))
+}
+
+/// Length of an overlap given as a CIGAR of a single match, or `*`
+fn overlap(line_no: usize, cigar: &str) -> Result<usize> {
+    if cigar == "*" {
+        return Ok(0);
+    }
+    cigar
+        .strip_suffix('M')
+        .and_then(|n| n.parse().ok())
+        .ok_or_else(|| gfa_error(line_no, &format!("unsupported overlap {cigar}")))
+}
+
+fn validate_overlap(graph: &HashGraph, link: &Link) -> core::result::Result<(), String> {
+    let from = graph.oriented(link.from, link.from_forward);
+    let to = graph.oriented(link.to, link.to_forward);
+
+    if from.is_empty() || to.is_empty() || link.overlap == 0 {
+        // nothing to check against
+        return Ok(());
+    }
+    if link.overlap > from.len() || link.overlap > to.len() {
+        return Err(format!(
+            "overlap of {} is longer than the linked segments",
+            link.overlap
+        ));
+    }
+    if from[from.len() - link.overlap..] != to[..link.overlap] {
+        return Err(format!(
+            "segments {} and {} do not overlap by {} bases",
+            graph.name(link.from),
+            graph.name(link.to),
+            link.overlap
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Debruijn, KmerTable};
+
+    #[test]
+    fn test_write_gfa() {
+        let mut graph: KmerTable<u32, 5> = KmerTable::default();
+        graph.add_seq(dna!("TTACGTAAG"));
+        graph.add_seq(dna!("TTACGTCAG"));
+        let unitigs = graph.compress();
+
+        let options = GfaOptions {
+            kmer_counts: true,
+            lengths: true,
+            colours: false,
+        };
+        let gfa = unitigs.graph.to_gfa(&options);
+        let lines: Vec<&str> = gfa.lines().collect();
+        let name = |seq: &str| {
+            let node = unitigs
+                .graph
+                .index
+                .iter()
+                .position(|s| s.to_string() == seq);
+            unitigs.graph.name(node.unwrap())
+        };
+        let (head, left, right) = (name("TTACGT"), name("ACGTAAG"), name("ACGTCAG"));
+
+        assert_eq!(lines[0], "H\tVN:Z:1.0");
+        assert_eq!(lines.iter().filter(|l| l.starts_with("S\t")).count(), 3);
+        assert!(lines.contains(&format!("S\t{left}\tACGTAAG\tLN:i:7\tKC:i:3").as_str()));
+        assert!(lines.contains(&format!("S\t{head}\tTTACGT\tLN:i:6\tKC:i:4").as_str()));
+        assert!(lines.contains(&format!("L\t{head}\t+\t{left}\t+\t4M").as_str()));
+        assert!(lines.contains(&format!("L\t{head}\t+\t{right}\t+\t4M").as_str()));
+    }
+
+    #[test]
+    fn test_write_gfa_orientation_and_colours() {
+        let mut graph = HashGraph::default();
+        let a = graph.add(dna!("ACGTT"));
+        let b = graph.add(dna!("GGAAC"));
+        graph.link(Link {
+            from: a,
+            from_forward: true,
+            to: b,
+            to_forward: false,
+            overlap: 3,
+        });
+        graph.samples = vec!["x".to_string(), "y".to_string()];
+        graph.colours.insert(a, vec![0, 1]);
+
+        let options = GfaOptions {
+            colours: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            graph.to_gfa(&options),
+            "H\tVN:Z:1.0\nS\t1\tACGTT\tSN:Z:x,y\nS\t2\tGGAAC\nL\t1\t+\t2\t-\t3M\n"
+        );
+
+        let round_trip = HashGraph::from_gfa(&graph.to_gfa(&options)).unwrap();
+        assert_eq!(round_trip.samples, graph.samples);
+        assert_eq!(round_trip.colours, graph.colours);
+    }
+
+    #[test]
+    fn test_read_gfa() {
+        let gfa = "H\tVN:Z:1.0\n\
+                   L\tu1\t+\tu2\t-\t3M\n\
+                   S\tu1\tACGTTCA\tKC:i:12\n\
+                   S\tu2\tATCCTGA\tLN:i:7\n\
+                   # comment\n\
+                   P\tp\tu1+,u2-\t3M\n";
+        let graph = HashGraph::from_gfa(gfa).unwrap();
+
+        assert_eq!(graph.len(), 2);
+        assert_eq!(graph.name(0), "u1");
+        assert_eq!(graph.counts.get(&0), Some(&12));
+        assert_eq!(
+            graph.links(0),
+            &[Link {
+                from: 0,
+                from_forward: true,
+                to: 1,
+                to_forward: false,
+                overlap: 3
+            }]
+        );
+        assert_eq!(
+            graph.paths,
+            vec![("p".to_string(), vec![(0, true), (1, false)])]
+        );
+
+        let round_trip = HashGraph::from_gfa(&graph.to_gfa(&GfaOptions::default())).unwrap();
+        assert_eq!(round_trip.index, graph.index);
+        assert_eq!(round_trip.links(0), graph.links(0));
+        assert_eq!(round_trip.paths, graph.paths);
+    }
+
+    #[test]
+    fn test_read_gfa_errors() {
+        let error = |gfa: &str| match HashGraph::from_gfa(gfa) {
+            Err(Error::Gfa { line, .. }) => line,
+            _ => panic!("expected a GFA error"),
+        };
+
+        assert_eq!(error("S\tu1\n"), 1);
+        assert_eq!(error("S\tu1\tACGT\nS\tu2\tACGX\n"), 2);
+        assert_eq!(error("S\tu1\tACGT\tLN:i:5\n"), 1);
+        assert_eq!(
+            HashGraph::from_gfa("S\tu1\tACGT\nS\tu2\tACNGT\n").err(),
+            Some(Error::Gfa {
+                line: 2,
+                reason: "segment u2 has unsupported base N at position 3".to_string()
+            })
+        );
+        assert_eq!(error("S\tu1\tACGT\nL\tu1\t+\tu3\t+\t0M\n"), 2);
+        assert_eq!(error("S\tu1\tACGT\nL\tu1\t+\tu1\t?\t0M\n"), 2);
+        assert_eq!(error("S\tu1\tACGT\nL\tu1\t+\tu1\t+\t2M1I\n"), 2);
+        // GT is not a prefix of ACGT
+        assert_eq!(error("S\tu1\tACGT\nS\tu2\tCGTA\nL\tu1\t+\tu2\t+\t2M\n"), 3);
+        assert!(HashGraph::from_gfa("S\tu1\tACGT\nS\tu2\tGTAA\nL\tu1\t+\tu2\t+\t2M\n").is_ok());
+    }
+}
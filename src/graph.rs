@@ -1,44 +1,238 @@
-use hashbrown::{hash_map, HashMap};
-use petgraph::visit::{GraphBase, IntoEdgeReferences};
+use std::collections::{HashMap, HashSet};
+
+use petgraph::visit::GraphBase;
 
 use bio_seq::prelude::*;
 
-use crate::{Edge, GenomeGraph, Path};
+use crate::{Debruijn, Node};
+
+/// An oriented edge between two sequences that overlap by `overlap` bases.
+/// A reverse orientation refers to the reverse complement of the sequence.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Link {
+    pub from: usize,
+    pub from_forward: bool,
+    pub to: usize,
+    pub to_forward: bool,
+    pub overlap: usize,
+}
 
-#[derive(Clone)]
+/// Sequence graph with a sequence per node and oriented, overlapping edges
+#[derive(Clone, Default)]
 pub struct HashGraph {
     pub index: Vec<Seq<Dna>>,
-    pub graph: HashMap<usize, usize>,
+    /// Links keyed by the node they leave from
+    pub graph: HashMap<usize, Vec<Link>>,
+    /// Total multiplicity of the k-mers of each node, when known
+    pub counts: HashMap<usize, usize>,
+    /// Indices into `samples` of the colours each node occurs in
+    pub colours: HashMap<usize, Vec<usize>>,
+    pub samples: Vec<String>,
+    /// Names of nodes that were given one, e.g. GFA segment names
+    pub names: HashMap<usize, String>,
+    /// Named walks through the graph as oriented nodes
+    pub paths: Vec<(String, Vec<(usize, bool)>)>,
 }
 
-impl<'a> IntoEdgeReferences for &'a HashGraph {
-    type EdgeRef = usize;
-    type EdgeReferences = hash_map::Values<'a, usize, usize>;
+impl GraphBase for HashGraph {
+    type NodeId = usize;
+    type EdgeId = usize;
+}
+
+impl HashGraph {
+    /// Push a copy of a sequence as a new node and return its id
+    pub fn add(&mut self, seq: &SeqSlice<Dna>) -> usize {
+        self.index.push(seq.to_owned());
+        self.index.len() - 1
+    }
+
+    pub fn link(&mut self, link: Link) {
+        let links = self.graph.entry(link.from).or_default();
+        if !links.contains(&link) {
+            links.push(link);
+        }
+    }
+
+    /// The sequence of a node, reverse complemented if not forward
+    pub fn oriented(&self, node: usize, forward: bool) -> Seq<Dna> {
+        if forward {
+            self.index[node].to_owned()
+        } else {
+            self.index[node].to_revcomp()
+        }
+    }
 
-    fn edge_references(self) -> Self::EdgeReferences {
-        self.edges.values()
+    /// Links leaving a node
+    pub fn links(&self, node: usize) -> &[Link] {
+        self.graph.get(&node).map_or(&[], Vec::as_slice)
+    }
+
+    /// Every link, ordered by the node it leaves from
+    pub fn all_links(&self) -> impl Iterator<Item = &Link> + '_ {
+        (0..self.index.len()).flat_map(|node| self.links(node))
+    }
+
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.graph.values().map(Vec::len).sum()
     }
 }
 
-impl GraphBase for HashGraph {
-    type NodeId = usize;
-    type EdgeId = usize;
+/// A de Bruijn graph compacted into maximal non-branching paths
+#[derive(Clone, Default)]
+pub struct Unitigs<const K: usize> {
+    pub graph: HashGraph,
+    /// Unitig and offset within it of every K-mer of the de Bruijn graph
+    pub positions: HashMap<Kmer<Dna, K>, (usize, usize)>,
 }
 
-impl Default for HashGraph {
-    fn default() -> Self {
-        unimplemented!()
+impl<const K: usize> Unitigs<K> {
+    pub fn get(&self, kmer: Kmer<Dna, K>) -> Option<(usize, usize)> {
+        self.positions.get(&kmer).copied()
     }
 }
 
-impl GenomeGraph for HashGraph {
-    fn add(&mut self, seq: &SeqSlice<Dna>) {
-        // push copy of seq into index Vec, and add it's index to
-        // the graph hashmap
-        unimplemented!()
+pub(crate) fn non_branching<G: Debruijn<K>, const K: usize>(graph: &G, node: Node<K>) -> bool {
+    graph.in_edges(node).count() == 1 && graph.out_edges(node).count() == 1
+}
+
+/// Compact a de Bruijn graph into unitigs linked by their K-1 overlaps.
+/// Unitigs start at every edge leaving a branching or dead-end node; the
+/// edges left over afterwards form isolated cycles.
+pub fn compress<G: Debruijn<K>, const K: usize>(graph: &G) -> Unitigs<K> {
+    let mut unitigs: Unitigs<K> = Unitigs::default();
+    let mut visited: HashSet<Kmer<Dna, K>> = HashSet::new();
+    // unitigs by their first and last node, for linking
+    let mut starts: HashMap<Node<K>, Vec<usize>> = HashMap::new();
+    let mut ends: HashMap<Node<K>, Vec<usize>> = HashMap::new();
+
+    let mut edges: Vec<Kmer<Dna, K>> = graph.kmers().map(|(kmer, _)| kmer).collect();
+    edges.sort_unstable();
+
+    let heads = edges
+        .iter()
+        .filter(|kmer| !non_branching(graph, Node::prefix(**kmer)));
+    let cycles = edges.iter();
+
+    for &first in heads.chain(cycles) {
+        if visited.contains(&first) {
+            continue;
+        }
+        let id = unitigs.graph.len();
+        let mut seq: Seq<Dna> = Node::prefix(first).to_seq();
+        let mut kmer = first;
+        let mut count: usize = 0;
+
+        loop {
+            visited.insert(kmer);
+            count += graph.multiplicity(kmer);
+            unitigs.positions.insert(kmer, (id, seq.len() + 1 - K));
+            let node = Node::suffix(kmer);
+            seq.push(node.last());
+
+            if !non_branching(graph, node) {
+                break;
+            }
+            match graph.out_edges(node).next() {
+                Some((next, _)) if !visited.contains(&next) => kmer = next,
+                _ => break,
+            }
+        }
+
+        unitigs.graph.add(&seq);
+        unitigs.graph.counts.insert(id, count);
+        starts.entry(Node::prefix(first)).or_default().push(id);
+        ends.entry(Node::suffix(kmer)).or_default().push(id);
+    }
+
+    for (node, from) in &ends {
+        for &to in starts.get(node).into_iter().flatten() {
+            for &from in from {
+                unitigs.graph.link(Link {
+                    from,
+                    from_forward: true,
+                    to,
+                    to_forward: true,
+                    overlap: K - 1,
+                });
+            }
+        }
+    }
+    for links in unitigs.graph.graph.values_mut() {
+        links.sort_unstable_by_key(|link| link.to);
+    }
+
+    unitigs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KmerTable;
+
+    fn unitig_strings<const K: usize>(unitigs: &Unitigs<K>) -> Vec<String> {
+        let mut seqs: Vec<String> = unitigs.graph.index.iter().map(|s| s.to_string()).collect();
+        seqs.sort();
+        seqs
+    }
+
+    #[test]
+    fn test_compress_linear() {
+        let seq = dna!("ACGAAACTTGTTGGCCCAGTG");
+        let mut graph: KmerTable<u32, 6> = KmerTable::default();
+        graph.add_seq(seq);
+
+        let unitigs = graph.compress();
+        assert_eq!(unitig_strings(&unitigs), vec![seq.to_string()]);
+        assert_eq!(unitigs.graph.edge_count(), 0);
+
+        for (offset, kmer) in seq.kmers::<6>().enumerate() {
+            assert_eq!(unitigs.get(kmer), Some((0, offset)));
+        }
+    }
+
+    #[test]
+    fn test_compress_bubble() {
+        // two paths diverge after ACGT and rejoin at AGGC
+        let mut graph: KmerTable<u32, 5> = KmerTable::default();
+        graph.add_seq(dna!("TTACGTAAGGCATT"));
+        graph.add_seq(dna!("TTACGTCAGGCATT"));
+
+        let unitigs = graph.compress();
+        assert_eq!(
+            unitig_strings(&unitigs),
+            vec!["ACGTAAGGC", "ACGTCAGGC", "AGGCATT", "TTACGT"]
+        );
+        assert_eq!(unitigs.graph.edge_count(), 4);
+        for link in unitigs.graph.all_links() {
+            let from = &unitigs.graph.index[link.from];
+            let to = &unitigs.graph.index[link.to];
+            assert_eq!(link.overlap, 4);
+            assert_eq!(&from[from.len() - 4..], &to[..4]);
+        }
+
+        let kmer: Kmer<Dna, 5> = Kmer::try_from(dna!("GGCAT")).unwrap();
+        let (id, offset) = unitigs.get(kmer).unwrap();
+        assert_eq!(unitigs.graph.index[id].to_string(), "AGGCATT");
+        assert_eq!(offset, 1);
     }
 
-    fn walk<'a>(&'a self, start: Self::NodeId) -> Path<'a, Self> {
-        unimplemented!()
+    #[test]
+    fn test_compress_cycle() {
+        let mut graph: KmerTable<u32, 5> = KmerTable::default();
+        graph.add_seq(dna!("ACGTTACGT"));
+
+        let unitigs = graph.compress();
+        assert_eq!(unitigs.graph.len(), 1);
+        assert_eq!(unitigs.graph.index[0].len(), 4 + 5);
+        assert_eq!(unitigs.positions.len(), 5);
+        assert_eq!(unitigs.graph.links(0).len(), 1);
     }
 }
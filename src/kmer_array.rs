@@ -1,15 +1,22 @@
 use std::collections;
+use std::iter::Enumerate;
 use std::ops::AddAssign;
+use std::slice;
 
+use petgraph::Direction;
 use petgraph::visit::{
-    GraphBase, IntoNeighbors, IntoNodeIdentifiers, NodeCount, NodeIndexable, Visitable,
+    Data, EdgeRef, GraphBase, IntoEdgeReferences, IntoEdges, IntoEdgesDirected, IntoNeighbors,
+    IntoNeighborsDirected, IntoNodeIdentifiers, NodeCount, NodeIndexable, Visitable,
 };
 
-use bio_seq::kmer::KmerIter;
 use bio_seq::prelude::*;
 
-use crate::{Debruijn, Edge, GenomeGraph, graph};
+use crate::debruijn::BASES;
+use crate::error::{Error, Result};
+use crate::{Debruijn, Edge, KmerIndex, Node, spectrum};
 
+/// Dense de Bruijn graph with a slot for each of the `4^K` possible edges.
+/// Nodes are the (K-1)-mers and are indexed by their 2-bit encoding.
 #[derive(Clone)]
 pub struct KmerArray<E: Edge, const K: usize> {
     pub index: Vec<E>, // this could [E; { 4**K }] if const expression are allowed
@@ -17,124 +24,391 @@ pub struct KmerArray<E: Edge, const K: usize> {
 }
 
 impl<E: Edge, const K: usize> GraphBase for KmerArray<E, K> {
-    type NodeId = Kmer<Dna, K>;
-    type EdgeId = E;
+    type NodeId = Node<K>;
+    type EdgeId = Kmer<Dna, K>;
 }
 
-/*
-impl<E: Edge, const K: usize> GraphRef for KmerArray<E, K> {
+impl<E: Edge, const K: usize> Data for KmerArray<E, K> {
+    type NodeWeight = ();
+    type EdgeWeight = E;
 }
-*/
 
-impl<const K: usize> NodeCount for KmerArray<u32, K> {
+impl<E: Edge, const K: usize> Default for KmerArray<E, K> {
+    fn default() -> Self {
+        KmerArray {
+            index: vec![E::default(); 1 << (K * Dna::BITS as usize)],
+            total: 0,
+        }
+    }
+}
+
+impl<E: Edge + AddAssign<E>, const K: usize> KmerIndex<K> for KmerArray<E, K> {
+    type Value = E;
+
+    fn contains_kmer(&self, kmer: Kmer<Dna, K>) -> bool {
+        self.index[usize::from(&kmer)] != E::default()
+    }
+
+    fn get_kmer(&self, kmer: Kmer<Dna, K>) -> Option<E> {
+        let count = self.index[usize::from(&kmer)];
+        if count != E::default() {
+            Some(count)
+        } else {
+            None
+        }
+    }
+
+    fn insert(&mut self, kmer: Kmer<Dna, K>, value: E) {
+        self.index[usize::from(&kmer)].add_assign(value);
+    }
+
+    /// Counts the occupied slots, which is linear in `4^K`
+    fn kmer_count(&self) -> usize {
+        self.index.iter().filter(|&&e| e != E::default()).count()
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (Kmer<Dna, K>, E)> + '_ {
+        self.index
+            .iter()
+            .enumerate()
+            .filter(|&(_, &e)| e != E::default())
+            .map(|(i, &e)| (Kmer::from(i), e))
+    }
+}
+
+impl<E: Edge + AddAssign<E>, const K: usize> KmerArray<E, K> {
+    /// The k-mer stored at a slot of the array
+    pub fn kmer(&self, index: usize) -> Result<Kmer<Dna, K>> {
+        if index < self.index.len() {
+            Ok(Kmer::from(index))
+        } else {
+            Err(Error::OutOfBounds {
+                index,
+                bound: self.index.len(),
+            })
+        }
+    }
+
+    pub fn add(&mut self, kmer: Kmer<Dna, K>) {
+        self.insert(kmer, E::from(true));
+        self.total += 1;
+    }
+
+    /// Shannon entropy of the K-mer frequencies in base 4
+    pub fn entropy(&self) -> f64 {
+        spectrum::entropy(self.index.iter().map(|e| e.multiplicity()))
+    }
+
+    /// Kullback–Leibler divergence in bits from another table, see
+    /// [`spectrum::kl_divergence`]
+    pub fn kld(&self, other: &Self, pseudocount: f64) -> f64 {
+        spectrum::kl_divergence(Debruijn::kmers(self), Debruijn::kmers(other), pseudocount)
+    }
+
+    /// Jensen–Shannon divergence in bits from another table, see
+    /// [`spectrum::js_divergence`]
+    pub fn jsd(&self, other: &Self, pseudocount: f64) -> f64 {
+        spectrum::js_divergence(Debruijn::kmers(self), Debruijn::kmers(other), pseudocount)
+    }
+}
+
+impl<E: Edge + AddAssign<E>, const K: usize> Debruijn<K> for KmerArray<E, K> {
+    fn add_seq(&mut self, seq: &SeqSlice<Dna>) {
+        for kmer in seq.kmers() {
+            self.add(kmer);
+        }
+    }
+
+    fn remove(&mut self, kmer: Kmer<Dna, K>) {
+        let slot = &mut self.index[usize::from(&kmer)];
+        self.total = self.total.saturating_sub(slot.multiplicity());
+        *slot = E::default();
+    }
+
+    fn multiplicity(&self, kmer: Kmer<Dna, K>) -> usize {
+        self.index[usize::from(&kmer)].multiplicity()
+    }
+
+    fn kmers(&self) -> impl Iterator<Item = (Kmer<Dna, K>, usize)> + '_ {
+        self.index
+            .iter()
+            .enumerate()
+            .map(|(i, e)| (Kmer::from(i), e.multiplicity()))
+            .filter(|&(_, m)| m > 0)
+    }
+}
+
+impl<E: Edge, const K: usize> KmerArray<E, K> {
+    fn occupied(&self, kmer: Kmer<Dna, K>) -> bool {
+        self.index[usize::from(&kmer)] != E::default()
+    }
+
+    fn has_edges(&self, node: Node<K>) -> bool {
+        BASES
+            .into_iter()
+            .any(|b| self.occupied(node.extend(b)) || self.occupied(node.prepend(b)))
+    }
+}
+
+/// Edges are identified by their K-mer, which determines both endpoints
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EdgeReference<'a, E: Edge, const K: usize> {
+    kmer: Kmer<Dna, K>,
+    weight: &'a E,
+}
+
+impl<E: Edge, const K: usize> EdgeRef for EdgeReference<'_, E, K> {
+    type NodeId = Node<K>;
+    type EdgeId = Kmer<Dna, K>;
+    type Weight = E;
+
+    fn source(&self) -> Node<K> {
+        Node::prefix(self.kmer)
+    }
+
+    fn target(&self) -> Node<K> {
+        Node::suffix(self.kmer)
+    }
+
+    fn weight(&self) -> &E {
+        self.weight
+    }
+
+    fn id(&self) -> Kmer<Dna, K> {
+        self.kmer
+    }
+}
+
+/// Occupied edges incident to a node, probing the four extensions in the
+/// given direction
+pub struct Edges<'a, E: Edge, const K: usize> {
+    graph: &'a KmerArray<E, K>,
+    node: Node<K>,
+    direction: Direction,
+    base: usize,
+}
+
+impl<'a, E: Edge, const K: usize> Iterator for Edges<'a, E, K> {
+    type Item = EdgeReference<'a, E, K>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(&base) = BASES.get(self.base) {
+            self.base += 1;
+            let kmer = match self.direction {
+                Direction::Outgoing => self.node.extend(base),
+                Direction::Incoming => self.node.prepend(base),
+            };
+            let weight = &self.graph.index[usize::from(&kmer)];
+            if *weight != E::default() {
+                return Some(EdgeReference { kmer, weight });
+            }
+        }
+        None
+    }
+}
+
+pub struct Neighbors<'a, E: Edge, const K: usize> {
+    edges: Edges<'a, E, K>,
+}
+
+impl<E: Edge, const K: usize> Iterator for Neighbors<'_, E, K> {
+    type Item = Node<K>;
+
+    fn next(&mut self) -> Option<Node<K>> {
+        let edge = self.edges.next()?;
+        Some(match self.edges.direction {
+            Direction::Outgoing => edge.target(),
+            Direction::Incoming => edge.source(),
+        })
+    }
+}
+
+pub struct EdgeReferences<'a, E: Edge, const K: usize> {
+    iter: Enumerate<slice::Iter<'a, E>>,
+}
+
+impl<'a, E: Edge, const K: usize> Iterator for EdgeReferences<'a, E, K> {
+    type Item = EdgeReference<'a, E, K>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter
+            .find(|(_, weight)| **weight != E::default())
+            .map(|(i, weight)| EdgeReference {
+                kmer: Kmer::from(i),
+                weight,
+            })
+    }
+}
+
+/// Nodes incident to at least one edge
+pub struct NodeIdentifiers<'a, E: Edge, const K: usize> {
+    graph: &'a KmerArray<E, K>,
+    index: usize,
+}
+
+impl<E: Edge, const K: usize> Iterator for NodeIdentifiers<'_, E, K> {
+    type Item = Node<K>;
+
+    fn next(&mut self) -> Option<Node<K>> {
+        while self.index < self.graph.node_bound() {
+            let node = self.graph.from_index(self.index);
+            self.index += 1;
+            if self.graph.has_edges(node) {
+                return Some(node);
+            }
+        }
+        None
+    }
+}
+
+impl<E: Edge, const K: usize> NodeCount for KmerArray<E, K> {
+    /// Number of nodes incident to at least one edge
     fn node_count(&self) -> usize {
-        self.index.len()
+        self.node_identifiers().count()
     }
 }
 
-impl<const K: usize> NodeIndexable for KmerArray<u32, K> {
+impl<E: Edge, const K: usize> NodeIndexable for KmerArray<E, K> {
     fn node_bound(&self) -> usize {
-        self.index.len()
+        self.index.len() >> Dna::BITS
     }
 
-    fn to_index(&self, kmer: Kmer<Dna, K>) -> usize {
-        usize::from(kmer)
+    fn to_index(&self, node: Node<K>) -> usize {
+        usize::from(node)
     }
 
-    fn from_index(&self, _index: usize) -> Kmer<Dna, K> {
-        unimplemented!()
+    fn from_index(&self, index: usize) -> Node<K> {
+        Node::try_from(index).expect("node index out of bounds")
     }
 }
 
-impl<'a, const K: usize> IntoNodeIdentifiers for &'a KmerArray<u32, K> {
-    type NodeIdentifiers = KmerIter<'a, Dna, K>;
+impl<'a, E: Edge, const K: usize> IntoNodeIdentifiers for &'a KmerArray<E, K> {
+    type NodeIdentifiers = NodeIdentifiers<'a, E, K>;
 
     fn node_identifiers(self) -> Self::NodeIdentifiers {
-        unimplemented!()
+        NodeIdentifiers {
+            graph: self,
+            index: 0,
+        }
     }
 }
 
-impl<'a, const K: usize> IntoNeighbors for &'a KmerArray<u32, K> {
-    type Neighbors = KmerIter<'a, Dna, K>;
+impl<'a, E: Edge, const K: usize> IntoNeighbors for &'a KmerArray<E, K> {
+    type Neighbors = Neighbors<'a, E, K>;
 
-    fn neighbors(self, _kmer: Kmer<Dna, K>) -> Self::Neighbors {
-        unimplemented!()
+    fn neighbors(self, node: Node<K>) -> Self::Neighbors {
+        self.neighbors_directed(node, Direction::Outgoing)
     }
 }
 
-impl<const K: usize> Visitable for KmerArray<u32, K> {
-    type Map = collections::HashSet<Kmer<Dna, K>>; // would prefer hashbrown::HashSet
-    fn visit_map(&self) -> Self::Map {
-        unimplemented!()
-    }
+impl<'a, E: Edge, const K: usize> IntoNeighborsDirected for &'a KmerArray<E, K> {
+    type NeighborsDirected = Neighbors<'a, E, K>;
 
-    fn reset_map(&self, _map: &mut Self::Map) {
-        unimplemented!()
+    fn neighbors_directed(self, node: Node<K>, direction: Direction) -> Self::NeighborsDirected {
+        Neighbors {
+            edges: self.edges_directed(node, direction),
+        }
     }
 }
 
-impl<E: Edge, const K: usize> Default for KmerArray<E, K> {
-    fn default() -> Self {
-        KmerArray {
-            index: vec![E::default(); 1 << (K * Dna::WIDTH as usize)],
-            total: 0,
+impl<'a, E: Edge, const K: usize> IntoEdgeReferences for &'a KmerArray<E, K> {
+    type EdgeRef = EdgeReference<'a, E, K>;
+    type EdgeReferences = EdgeReferences<'a, E, K>;
+
+    fn edge_references(self) -> Self::EdgeReferences {
+        EdgeReferences {
+            iter: self.index.iter().enumerate(),
         }
     }
 }
 
-impl<E: Edge + AddAssign<E>, const K: usize> GenomeGraph for KmerArray<E, K> {
-    fn add(&mut self, kmer: Kmer<Dna, K>) {
-        self.index[usize::from(kmer)].add_assign(E::from(1u8));
-        self.total += 1;
+impl<'a, E: Edge, const K: usize> IntoEdges for &'a KmerArray<E, K> {
+    type Edges = Edges<'a, E, K>;
+
+    fn edges(self, node: Node<K>) -> Self::Edges {
+        self.edges_directed(node, Direction::Outgoing)
     }
+}
 
-    fn walk(&self, _start: Kmer<Dna, K>) {
-        unimplemented!()
+impl<'a, E: Edge, const K: usize> IntoEdgesDirected for &'a KmerArray<E, K> {
+    type EdgesDirected = Edges<'a, E, K>;
+
+    fn edges_directed(self, node: Node<K>, direction: Direction) -> Self::EdgesDirected {
+        Edges {
+            graph: self,
+            node,
+            direction,
+            base: 0,
+        }
     }
 }
 
-impl<E: Edge + AddAssign<E>, const K: usize> Debruijn<K> for KmerArray<E, K>
-where
-    f64: From<E>,
-{
-    fn compress(
-        &self,
-    ) -> graph::HashGraph
-    {
-        unimplemented!()
-        //        let start: SeqSlice<Dna> = node.heads();
-        //let g: GenomeGraph = GenomeGraph::default();
-        //g
+impl<E: Edge, const K: usize> Visitable for KmerArray<E, K> {
+    type Map = collections::HashSet<Node<K>>; // would prefer hashbrown::HashSet
+
+    fn visit_map(&self) -> Self::Map {
+        collections::HashSet::new()
     }
 
-    fn eulerian(&self) -> bool {
-        unimplemented!()
+    fn reset_map(&self, map: &mut Self::Map) {
+        map.clear();
     }
 }
 
-impl<E: Edge + AddAssign<E>, const K: usize> KmerArray<E, K> {
-    fn entropy(&self) -> f64 {
-        let mut h: f64 = 0.0;
-        let t: f64 = self.total as f64;
-        for i in 0..self.index.len() {
-            let p: f64 = f64::from(self.index[i]) / t;
-            if p > 0.0 {
-                h += p * p.ln();
-            }
-        }
-        h
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use petgraph::algo::{dijkstra, kosaraju_scc, toposort};
+    use petgraph::visit::Dfs;
+
+    fn node(seq: &SeqSlice<Dna>) -> Node<4> {
+        Node::try_from(seq).unwrap()
     }
 
-    fn kld(&self, other: &Self) -> f64 {
-        let mut h: f64 = 0.0;
-        let t: f64 = self.total as f64;
-        for i in 0..self.index.len() {
-            let p: f64 = f64::from(self.index[i]) / t;
-            let q: f64 = f64::from(other.index[i]) / t;
-            if p > 0.0 && q > 0.0 {
-                h += q * p.ln();
-            }
+    #[test]
+    fn test_petgraph_traversal() {
+        let mut graph: KmerArray<u32, 4> = KmerArray::default();
+        graph.add_seq(dna!("ACGTTACG"));
+        graph.add_seq(dna!("GGCAT"));
+
+        // ACG -> CGT -> GTT -> TTA -> TAC -> ACG is a cycle
+        let sccs = kosaraju_scc(&graph);
+        assert_eq!(sccs.iter().filter(|scc| scc.len() == 5).count(), 1);
+        assert_eq!(sccs.len(), 1 + 3);
+        assert_eq!(graph.node_count(), 8);
+        assert!(toposort(&graph, None).is_err());
+
+        let mut dfs = Dfs::new(&graph, node(dna!("ACG")));
+        let mut reached = 0;
+        while dfs.next(&graph).is_some() {
+            reached += 1;
         }
-        h
+        assert_eq!(reached, 5);
+
+        let distances = dijkstra(&graph, node(dna!("ACG")), None, |e| *e.weight());
+        assert_eq!(distances[&node(dna!("TAC"))], 4);
+        assert!(!distances.contains_key(&node(dna!("GGC"))));
+
+        // petgraph's `edges` and the K-mers of `Debruijn` are both in scope
+        assert_eq!((&graph).edges(node(dna!("ACG"))).count(), 1);
+        assert_eq!(graph.kmers().count(), 5 + 2);
+    }
+
+    #[test]
+    fn test_toposort_acyclic() {
+        let mut graph: KmerArray<u32, 4> = KmerArray::default();
+        graph.add_seq(dna!("GGCATT"));
+
+        let order: Vec<String> = toposort(&graph, None)
+            .unwrap()
+            .iter()
+            .map(|n| n.to_string())
+            .collect();
+        assert_eq!(order, vec!["GGC", "GCA", "CAT", "ATT"]);
+
+        let incoming: Vec<Node<4>> = graph
+            .neighbors_directed(node(dna!("CAT")), Direction::Incoming)
+            .collect();
+        assert_eq!(incoming, vec![node(dna!("GCA"))]);
     }
 }
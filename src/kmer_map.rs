@@ -4,27 +4,58 @@ use std::collections::HashMap;
 
 use bio_seq::prelude::*;
 
-use crate::alignment;
+use crate::error::{Error, Result};
+use crate::{KmerIndex, alignment};
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct KmerMap<const K: usize> {
     pub index: HashMap<Kmer<Dna, K>, i32>,
     pub len: u32,
+    /// Only reference k-mers starting at multiples of `stride` are indexed
+    #[serde(default = "default_stride")]
+    pub stride: u32,
+}
+
+fn default_stride() -> u32 {
+    1
 }
 
 impl<const K: usize> KmerMap<K> {
-    pub fn new(seq: &SeqSlice<Dna>) -> Self {
+    /// Positions are stored as signed 1-based offsets, so the reference
+    /// must be shorter than `i32::MAX`
+    pub fn new(seq: &SeqSlice<Dna>) -> Result<Self> {
+        Self::sampled(seq, 1)
+    }
+
+    /// Index only the k-mers at every `stride`-th reference position, on
+    /// both strands. K-mers that also occur at an unsampled position are
+    /// still marked as ambiguous.
+    pub fn sampled(seq: &SeqSlice<Dna>, stride: u32) -> Result<Self> {
         let mut index: HashMap<Kmer<Dna, K>, i32> = HashMap::new();
-        let len: i32 = seq.len() as i32;
+        let len: i32 = seq.len().try_into().map_err(|_| Error::SequenceTooLong {
+            max: i32::MAX as usize,
+            got: seq.len(),
+        })?;
+        let stride: usize = stride.max(1) as usize;
+        let revcomp = seq.to_revcomp();
+
+        // forward position of the k-mer at `pos` on the reverse strand
+        let sampled_rc = |pos: usize| (seq.len() - pos - K).is_multiple_of(stride);
 
         for (pos, kmer) in seq.kmers().enumerate() {
+            if !pos.is_multiple_of(stride) {
+                continue;
+            }
             if let Some(v) = index.get_mut(&kmer) {
                 *v = 0;
             } else {
                 index.insert(kmer, pos as i32 + 1);
             }
         }
-        for (pos, kmer) in seq.to_revcomp().kmers().enumerate() {
+        for (pos, kmer) in revcomp.kmers().enumerate() {
+            if !sampled_rc(pos) {
+                continue;
+            }
             if let Some(v) = index.get_mut(&kmer) {
                 *v = 0;
             } else {
@@ -32,22 +63,42 @@ impl<const K: usize> KmerMap<K> {
             }
         }
 
-        KmerMap {
+        if stride > 1 {
+            // a k-mer repeated at an unsampled position is not unique
+            let forward = seq
+                .kmers()
+                .enumerate()
+                .filter(|(pos, _)| !pos.is_multiple_of(stride));
+            let reverse = revcomp
+                .kmers()
+                .enumerate()
+                .filter(|(pos, _)| !sampled_rc(*pos));
+
+            for (_, kmer) in forward.chain(reverse) {
+                if let Some(v) = index.get_mut(&kmer) {
+                    *v = 0;
+                }
+            }
+        }
+
+        Ok(KmerMap {
             index,
             len: len as u32,
-        }
+            stride: stride as u32,
+        })
     }
 
-    pub fn contains(&self, seq: &SeqSlice<Dna>) -> bool {
-        if seq.len() != K {
-            panic!();
-        }
+    /// Whether a stored position is ambiguous or is one `sampled` could
+    /// have stored for a k-mer of a reference of length `len`
+    fn valid(&self, value: i32) -> bool {
+        let (len, k) = (i64::from(self.len), K as i64);
+        let value = i64::from(value);
+        value == 0 || (1..=len - k + 1).contains(&value) || (1 - len..=1 - k).contains(&value)
+    }
 
-        if let Ok(kmer) = Kmer::try_from(seq) {
-            self.index.contains_key(&kmer)
-        } else {
-            false
-        }
+    pub fn contains(&self, seq: &SeqSlice<Dna>) -> Result<bool> {
+        let kmer: Kmer<Dna, K> = Kmer::try_from(seq)?;
+        Ok(self.index.contains_key(&kmer))
     }
 
     pub fn matches(&self, seq: &SeqSlice<Dna>) -> (u32, u32) {
@@ -64,24 +115,110 @@ impl<const K: usize> KmerMap<K> {
     }
 
     pub fn match_kmers(&self, seq: &SeqSlice<Dna>) -> Vec<Option<i32>> {
-        let mut mapping: Vec<Option<i32>> = Vec::new();
+        self.iter_matches(seq).collect()
+    }
 
-        if seq.len() < K {
-            // this may better be an exception
-            return mapping;
-        }
+    /// Lazily look up the reference position of each k-mer of the query
+    pub fn iter_matches<'a>(
+        &'a self,
+        seq: &'a SeqSlice<Dna>,
+    ) -> impl Iterator<Item = Option<i32>> + 'a {
+        // queries shorter than K yield no k-mers
+        seq.kmers::<K>().map(|kmer| self.index.get(&kmer).copied())
+    }
+}
 
-        for kmer in seq.kmers() {
-            mapping.push(self.index.get(&kmer).copied());
+impl<const K: usize> KmerIndex<K> for KmerMap<K> {
+    type Value = i32;
+
+    fn contains_kmer(&self, kmer: Kmer<Dna, K>) -> bool {
+        self.index.contains_key(&kmer)
+    }
+
+    fn get_kmer(&self, kmer: Kmer<Dna, K>) -> Option<i32> {
+        self.index.get(&kmer).copied()
+    }
+
+    /// A k-mer inserted at a second, different position becomes ambiguous
+    /// (0). A position that does not place the k-mer inside the reference,
+    /// on either strand, is rejected and leaves the index unchanged, so
+    /// every hit is a valid reference interval.
+    fn insert(&mut self, kmer: Kmer<Dna, K>, value: i32) {
+        if !self.valid(value) {
+            return;
         }
-        mapping
+        if let Some(v) = self.index.get_mut(&kmer) {
+            if *v != value {
+                *v = 0;
+            }
+        } else {
+            self.index.insert(kmer, value);
+        }
+    }
+
+    fn kmer_count(&self) -> usize {
+        self.index.len()
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (Kmer<Dna, K>, i32)> + '_ {
+        self.index.iter().map(|(kmer, pos)| (*kmer, *pos))
     }
 }
 
 impl<const K: usize, A: alignment::QuasiAlignment + Debug> alignment::QuasiAlign<A> for KmerMap<K> {
     fn quasi_align(&self, seq: &SeqSlice<Dna>, gap: u32) -> Vec<A> {
-        let v: Vec<Option<i32>> = self.match_kmers(seq);
-        let x: Vec<A> = alignment::merge_segments(v, K as u32 - 1);
-        alignment::merge_contigs(x, gap)
+        self.quasi_align_iter(seq, gap).collect()
+    }
+
+    fn quasi_align_iter<'a>(
+        &'a self,
+        seq: &'a SeqSlice<Dna>,
+        gap: u32,
+    ) -> impl Iterator<Item = A> + 'a
+    where
+        A: 'a,
+    {
+        // `KmerIndex::insert` rejects positions outside the reference, so
+        // only a hand-edited or corrupt `index` has invalid hits to skip
+        let segments =
+            alignment::Segments::sampled(self.iter_matches(seq), K as u32 - 1, self.stride)
+                .filter_map(|segment| segment.ok());
+        alignment::Contigs::new(segments, gap)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contains_wrong_length() {
+        let map: KmerMap<5> = KmerMap::new(dna!("ACGTGACGGTCGTACCACCAAAGT")).unwrap();
+
+        assert_eq!(map.contains(dna!("GACGG")), Ok(true));
+        assert_eq!(map.contains(dna!("CCCCC")), Ok(false));
+        assert_eq!(
+            map.contains(dna!("GACG")),
+            Err(Error::MismatchedLength {
+                expected: 5,
+                got: 4
+            })
+        );
+    }
+
+    #[test]
+    fn test_insert_out_of_range() {
+        let mut map: KmerMap<4> = KmerMap::new(dna!("ACGTACGT")).unwrap();
+        let kmer: Kmer<Dna, 4> = Kmer::try_from(dna!("GATT")).unwrap();
+
+        // forward k-mers start at 1..=5 and reverse ones end at -7..=-3
+        for value in [6, -2, -8, i32::MAX, i32::MIN] {
+            map.insert(kmer, value);
+            assert_eq!(map.get_kmer(kmer), None);
+        }
+        map.insert(kmer, -3);
+        assert_eq!(map.get_kmer(kmer), Some(-3));
+        map.insert(kmer, 5);
+        assert_eq!(map.get_kmer(kmer), Some(0));
     }
 }
@@ -3,6 +3,7 @@ use std::collections::HashSet;
 use bio_seq::prelude::*;
 
 //use crate::{Debruijn, Edge, GenomeGraph};
+use crate::KmerIndex;
 
 #[derive(Clone)]
 pub struct KmerSet<const K: usize> {
@@ -49,6 +50,30 @@ impl<const K: usize> KmerSet<K> {
     }
 }
 
+impl<const K: usize> KmerIndex<K> for KmerSet<K> {
+    type Value = ();
+
+    fn contains_kmer(&self, kmer: Kmer<Dna, K>) -> bool {
+        self.index.contains(&kmer)
+    }
+
+    fn get_kmer(&self, kmer: Kmer<Dna, K>) -> Option<()> {
+        self.index.get(&kmer).map(|_| ())
+    }
+
+    fn insert(&mut self, kmer: Kmer<Dna, K>, _value: ()) {
+        self.index.insert(kmer);
+    }
+
+    fn kmer_count(&self) -> usize {
+        self.index.len()
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (Kmer<Dna, K>, ())> + '_ {
+        self.index.iter().map(|kmer| (*kmer, ()))
+    }
+}
+
 /*
 impl<const K: usize> Debruijn<K> for KmerSet<K>
 {
@@ -1,12 +1,12 @@
+use std::collections::HashMap;
 use std::ops::AddAssign;
 
-use hashbrown::HashMap;
 use petgraph::visit::GraphBase;
 
 //use bio_seq::kmer::KmerIter;
 use bio_seq::prelude::*;
 
-use crate::{graph, Debruijn, Edge, GenomeGraph};
+use crate::{Debruijn, Edge, KmerIndex, Node, spectrum};
 
 #[derive(Clone)]
 pub struct KmerTable<E: Edge, const K: usize> {
@@ -15,8 +15,8 @@ pub struct KmerTable<E: Edge, const K: usize> {
 }
 
 impl<E: Edge, const K: usize> GraphBase for KmerTable<E, K> {
-    type NodeId = Kmer<Dna, K>;
-    type EdgeId = E;
+    type NodeId = Node<K>;
+    type EdgeId = Kmer<Dna, K>;
 }
 
 impl<E: Edge, const K: usize> Default for KmerTable<E, K> {
@@ -33,68 +33,117 @@ impl<'a, E: Edge, const K: usize> GraphRef for &'a KmerTable<E, K> {
 }
 */
 
-impl<E: Edge + AddAssign, const K: usize> GenomeGraph for KmerTable<E, K>
-where
-    f64: From<E>,
-{
-    fn add(&mut self, kmer: Kmer<Dna, K>) {
-        let entry = self.index.entry(kmer).or_default();
-        entry.add_assign(E::from(1u8));
-        self.total += 1;
+impl<E: Edge + AddAssign, const K: usize> KmerIndex<K> for KmerTable<E, K> {
+    type Value = E;
+
+    fn contains_kmer(&self, kmer: Kmer<Dna, K>) -> bool {
+        self.index.contains_key(&kmer)
+    }
+
+    fn get_kmer(&self, kmer: Kmer<Dna, K>) -> Option<E> {
+        self.index.get(&kmer).copied()
+    }
+
+    fn insert(&mut self, kmer: Kmer<Dna, K>, value: E) {
+        self.index.entry(kmer).or_default().add_assign(value);
+    }
+
+    fn kmer_count(&self) -> usize {
+        self.index.len()
     }
 
-    fn walk(&self, _start: Kmer<Dna, K>) {
-        unimplemented!()
+    fn iter(&self) -> impl Iterator<Item = (Kmer<Dna, K>, E)> + '_ {
+        self.index.iter().map(|(kmer, count)| (*kmer, *count))
     }
 }
 
-impl<E: Edge + AddAssign, const K: usize> Debruijn<K> for KmerTable<E, K>
-where
-    f64: From<E>,
-{
-    fn compress(&self) -> graph::HashGraph {
-        unimplemented!()
+impl<E: Edge + AddAssign, const K: usize> KmerTable<E, K> {
+    /// Shannon entropy of the K-mer frequencies in base 4
+    pub fn entropy(&self) -> f64 {
+        spectrum::entropy(self.index.values().map(|e| e.multiplicity()))
+    }
+
+    /// Kullback–Leibler divergence in bits from another table, see
+    /// [`spectrum::kl_divergence`]
+    pub fn kld(&self, other: &Self, pseudocount: f64) -> f64 {
+        spectrum::kl_divergence(Debruijn::kmers(self), Debruijn::kmers(other), pseudocount)
     }
 
-    fn eulerian(&self) -> bool {
-        unimplemented!()
+    /// Jensen–Shannon divergence in bits from another table, see
+    /// [`spectrum::js_divergence`]
+    pub fn jsd(&self, other: &Self, pseudocount: f64) -> f64 {
+        spectrum::js_divergence(Debruijn::kmers(self), Debruijn::kmers(other), pseudocount)
+    }
+
+    pub fn add(&mut self, kmer: Kmer<Dna, K>) {
+        self.insert(kmer, E::from(true));
+        self.total += 1;
     }
 }
 
-impl<E: Edge + AddAssign, const K: usize> KmerTable<E, K>
-where
-    f64: From<E>,
-{
-    fn entropy(&self) -> f64 {
-        let mut h: f64 = 0.0;
-        let mut t: f64 = 0.0;
-        for count in self.index.values() {
-            t += f64::from(*count);
-        }
-        for count in self.index.values() {
-            let p: f64 = f64::from(*count) / t;
-            if p > 0.0 {
-                h += p * p.ln();
-            }
+impl<E: Edge + AddAssign, const K: usize> Debruijn<K> for KmerTable<E, K> {
+    fn add_seq(&mut self, seq: &SeqSlice<Dna>) {
+        for kmer in seq.kmers() {
+            self.add(kmer);
         }
-        h
     }
 
-    fn kld(&self, other: &Self) -> f64 {
-        let mut h: f64 = 0.0;
-        let t_p: f64 = self.total as f64;
-        let t_q: f64 = other.total as f64;
-        if t_p == 0.0 || t_q == 0.0 {
-            return 0.0;
+    fn remove(&mut self, kmer: Kmer<Dna, K>) {
+        if let Some(count) = self.index.remove(&kmer) {
+            self.total = self.total.saturating_sub(count.multiplicity());
         }
+    }
 
-        for (kmer, c_p) in self.index.iter() {
-            if let Some(c_q) = other.index.get(kmer) {
-                let p: f64 = f64::from(*c_p) / t_p;
-                let q: f64 = f64::from(*c_q) / t_q;
-                h += q * p.ln();
-            }
-        }
-        h
+    fn multiplicity(&self, kmer: Kmer<Dna, K>) -> usize {
+        self.index.get(&kmer).map_or(0, |e| e.multiplicity())
+    }
+
+    fn kmers(&self) -> impl Iterator<Item = (Kmer<Dna, K>, usize)> + '_ {
+        self.index
+            .iter()
+            .map(|(kmer, e)| (*kmer, e.multiplicity()))
+            .filter(|&(_, m)| m > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_debruijn_neighbours() {
+        let mut graph: KmerTable<u32, 4> = KmerTable::default();
+        graph.add_seq(dna!("ACGTTACGTA"));
+
+        let node = Node::try_from(dna!("CGT")).unwrap();
+        let succ: Vec<String> = graph.successors(node).map(|n| n.to_string()).collect();
+        let pred: Vec<String> = graph.predecessors(node).map(|n| n.to_string()).collect();
+        assert_eq!(succ, vec!["GTA", "GTT"]);
+        assert_eq!(pred, vec!["ACG"]);
+
+        let kmer: Kmer<Dna, 4> = Kmer::try_from(dna!("ACGT")).unwrap();
+        assert_eq!(graph.multiplicity(kmer), 2);
+        assert_eq!(graph.in_degree(node), 2);
+        assert_eq!(graph.out_degree(node), 2);
+        assert_eq!(graph.total, 7);
+
+        // ACG CGT GTT TTA TAC GTA
+        assert_eq!(graph.nodes().len(), 6);
+        assert_eq!(graph.kmers().count(), 6);
+    }
+
+    #[test]
+    fn test_divergence() {
+        let mut p: KmerTable<u32, 4> = KmerTable::default();
+        let mut q: KmerTable<u32, 4> = KmerTable::default();
+        p.add_seq(dna!("ACGTTACGTA"));
+        q.add_seq(dna!("ACGTTACGTA"));
+        assert_eq!(p.kld(&q, 0.0), 0.0);
+
+        q.add_seq(dna!("GGCC"));
+        assert!(p.kld(&q, 0.0) > 0.0);
+        assert_eq!(q.kld(&p, 0.0), f64::INFINITY);
+        assert!(q.kld(&p, 1.0).is_finite());
+        assert!((p.jsd(&q, 1.0) - q.jsd(&p, 1.0)).abs() < 1e-12);
     }
 }
@@ -1,45 +1,186 @@
 pub mod alignment;
-//mod graph;
-//mod kmer_array;
+pub mod bidirected;
+pub mod bubble;
+pub mod classify;
+pub mod clean;
+mod colour_class;
+mod coloured;
+pub mod compare;
+pub mod dag;
+mod debruijn;
+pub mod dot;
+pub mod error;
+pub mod eulerian;
+pub mod gfa;
+pub mod graph;
+mod kmer_array;
 mod kmer_map;
 mod kmer_set;
-//mod kmer_table;
+mod kmer_table;
+pub mod matrix;
+mod poa;
+pub mod spectrum;
 
-pub use alignment::{Alignment, QuasiAlign};
-//pub use kmer_array::KmerArray;
+pub use alignment::{Alignment, Contigs, QuasiAlign, Segments};
+pub use bidirected::Bidirected;
+pub use bubble::{Branch, Bubble, Variant};
+pub use classify::{Call, Classifier};
+pub use clean::{Pass, Removed};
+pub use colour_class::{ColourClasses, ColourSet};
+pub use coloured::{ColourStats, Coloured};
+pub use compare::Comparison;
+pub use dag::Dag;
+pub use debruijn::{Debruijn, Node};
+pub use error::{Error, Result};
+pub use eulerian::{Path, VirtualEdge};
+pub use graph::{HashGraph, Link, Unitigs};
+pub use kmer_array::KmerArray;
 pub use kmer_map::KmerMap;
 pub use kmer_set::KmerSet;
-//pub use kmer_set::KmerSet;
-//pub use kmer_table::KmerTable;
+pub use kmer_table::KmerTable;
+pub use matrix::{Coo, Csr};
+pub use poa::Poa;
+pub use spectrum::{KStats, Scan};
 
 //use petgraph::visit::{GraphBase, IntoEdgeReferences};
 
 use bio_seq::prelude::*;
 
-pub trait Edge: Default + Copy + PartialEq + Clone + From<bool> {}
+pub trait Edge: Default + Copy + PartialEq + Clone + From<bool> {
+    /// Number of times the edge was observed
+    fn multiplicity(self) -> usize;
+}
+
+impl Edge for u32 {
+    fn multiplicity(self) -> usize {
+        self as usize
+    }
+}
+
+impl Edge for u16 {
+    fn multiplicity(self) -> usize {
+        self as usize
+    }
+}
+
+impl Edge for u8 {
+    fn multiplicity(self) -> usize {
+        self as usize
+    }
+}
+
+impl Edge for usize {
+    fn multiplicity(self) -> usize {
+        self
+    }
+}
+
+impl Edge for bool {
+    fn multiplicity(self) -> usize {
+        self as usize
+    }
+}
+
+/// Common interface of the k-mer containers so that algorithms can be
+/// written once and run on any backend
+pub trait KmerIndex<const K: usize> {
+    /// Value associated with each k-mer, e.g. a count or a reference position
+    type Value: Copy;
+
+    fn contains_kmer(&self, kmer: Kmer<Dna, K>) -> bool;
+
+    fn get_kmer(&self, kmer: Kmer<Dna, K>) -> Option<Self::Value>;
+
+    /// Merge a value into the index. Counting containers add to the
+    /// existing count, sets ignore the value.
+    fn insert(&mut self, kmer: Kmer<Dna, K>, value: Self::Value);
 
-impl Edge for u32 {}
-impl Edge for u16 {}
-impl Edge for u8 {}
-impl Edge for usize {}
-impl Edge for bool {}
+    /// Number of distinct k-mers in the index
+    fn kmer_count(&self) -> usize;
 
-//pub struct Path<'a, G: GenomeGraph> {
-//    graph: &'a G,
-//    edges: Vec<G::EdgeRef>,
-//}
+    fn is_empty(&self) -> bool {
+        self.kmer_count() == 0
+    }
 
-//pub trait GenomeGraph: GraphBase + IntoEdgeReferences {
-//    fn add(&mut self, seq: &SeqSlice<Dna>);
-//    fn walk(&self, start: Self::NodeId) -> Path<Self>;
-//}
+    fn iter(&self) -> impl Iterator<Item = (Kmer<Dna, K>, Self::Value)> + '_;
 
-//pub trait Debruijn<const K: usize>: GenomeGraph {
-//    fn compress(&self) -> graph::HashGraph;
-//    fn eulerian(&self) -> bool;
-//}
+    /// Fraction of the k-mers of a sequence that are in the index
+    fn containment(&self, seq: &SeqSlice<Dna>) -> f64 {
+        let mut matches = 0;
+        let mut total = 0;
+
+        for kmer in seq.kmers::<K>() {
+            if self.contains_kmer(kmer) {
+                matches += 1;
+            }
+            total += 1;
+        }
+
+        if total > 0 {
+            matches as f64 / total as f64
+        } else {
+            0.0
+        }
+    }
+}
 
 pub trait RankSelect {
     fn rank(reference: &SeqSlice<Dna>, query: &SeqSlice<Dna>) -> usize;
     fn select(reference: &SeqSlice<Dna>, query: &SeqSlice<Dna>, rank: usize) -> usize;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count_shared<const K: usize, I: KmerIndex<K>, J: KmerIndex<K>>(a: &I, b: &J) -> usize {
+        a.iter().filter(|(kmer, _)| b.contains_kmer(*kmer)).count()
+    }
+
+    #[test]
+    fn test_kmer_index_backends() {
+        let seq = dna!("ACGTGACGGTCGTACCACCAAAGT");
+        let query = dna!("GACGGTCGTAC");
+
+        let set: KmerSet<4> = KmerSet::new(seq);
+        let map: KmerMap<4> = KmerMap::new(seq).unwrap();
+        let mut table: KmerTable<u32, 4> = KmerTable::default();
+        let mut array: KmerArray<u32, 4> = KmerArray::default();
+        for kmer in seq.kmers::<4>().chain(seq.to_revcomp().kmers::<4>()) {
+            table.add(kmer);
+            array.add(kmer);
+        }
+
+        assert_eq!(set.kmer_count(), map.index.len());
+        assert_eq!(table.kmer_count(), array.kmer_count());
+        assert_eq!(count_shared(&set, &map), set.kmer_count());
+        assert_eq!(count_shared(&table, &array), set.kmer_count());
+
+        assert_eq!(set.containment(query), 1.0);
+        assert_eq!(map.containment(query), 1.0);
+        assert_eq!(table.containment(query), 1.0);
+        assert_eq!(array.containment(query), 1.0);
+        assert_eq!(array.containment(dna!("CCCCCC")), 0.0);
+
+        let kmer: Kmer<Dna, 4> = Kmer::try_from(dna!("ACGT")).unwrap();
+        assert_eq!(table.get_kmer(kmer), array.get_kmer(kmer));
+        assert_eq!(map.get_kmer(kmer), Some(0));
+    }
+
+    #[test]
+    fn test_kmer_index_insert() {
+        let kmer: Kmer<Dna, 4> = Kmer::try_from(dna!("GATT")).unwrap();
+
+        let mut map: KmerMap<4> = KmerMap::new(dna!("ACGTACGT")).unwrap();
+        map.insert(kmer, 3);
+        assert_eq!(map.get_kmer(kmer), Some(3));
+        map.insert(kmer, 5);
+        assert_eq!(map.get_kmer(kmer), Some(0));
+
+        let mut array: KmerArray<u32, 4> = KmerArray::default();
+        array.insert(kmer, 2);
+        array.insert(kmer, 3);
+        assert_eq!(array.get_kmer(kmer), Some(5));
+        assert!(array.iter().eq([(kmer, 5)]));
+    }
+}
@@ -0,0 +1,297 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::error::{Error, Result};
+use crate::{Debruijn, Node};
+
+/// Sparse matrix as `(row, column, value)` triples. The matrices built here
+/// are sorted by row then column with no explicit zeros, but entries added
+/// by hand may come in any order.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Coo {
+    pub rows: usize,
+    pub cols: usize,
+    pub entries: Vec<(usize, usize, f64)>,
+}
+
+/// Sparse matrix in compressed sparse row format. The entries of row `i`
+/// are `indices[indptr[i]..indptr[i + 1]]` with the matching `values`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Csr {
+    pub rows: usize,
+    pub cols: usize,
+    pub indptr: Vec<usize>,
+    pub indices: Vec<usize>,
+    pub values: Vec<f64>,
+}
+
+/// Write a Matrix Market coordinate header and `nnz` 1-based entries
+fn write_matrix_market<W: Write>(
+    out: &mut W,
+    rows: usize,
+    cols: usize,
+    nnz: usize,
+    entries: impl Iterator<Item = (usize, usize, f64)>,
+) -> io::Result<()> {
+    writeln!(out, "%%MatrixMarket matrix coordinate real general")?;
+    writeln!(out, "{rows} {cols} {nnz}")?;
+    for (i, j, value) in entries {
+        writeln!(out, "{} {} {value}", i + 1, j + 1)?;
+    }
+    Ok(())
+}
+
+impl Coo {
+    /// Number of stored entries
+    pub fn nnz(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Compressed rows of the matrix. Entries are sorted and duplicates of
+    /// the same position summed; an entry outside the matrix is an
+    /// `OutOfBounds` error.
+    pub fn to_csr(&self) -> Result<Csr> {
+        for &(i, j, _) in &self.entries {
+            if i >= self.rows {
+                return Err(Error::OutOfBounds {
+                    index: i,
+                    bound: self.rows,
+                });
+            }
+            if j >= self.cols {
+                return Err(Error::OutOfBounds {
+                    index: j,
+                    bound: self.cols,
+                });
+            }
+        }
+        let mut entries = self.entries.clone();
+        entries.sort_by_key(|&(i, j, _)| (i, j));
+        entries.dedup_by(|next, kept| {
+            let duplicate = (next.0, next.1) == (kept.0, kept.1);
+            if duplicate {
+                kept.2 += next.2;
+            }
+            duplicate
+        });
+
+        let mut indptr = vec![0; self.rows + 1];
+        for &(i, _, _) in &entries {
+            indptr[i + 1] += 1;
+        }
+        for i in 0..self.rows {
+            indptr[i + 1] += indptr[i];
+        }
+        Ok(Csr {
+            rows: self.rows,
+            cols: self.cols,
+            indptr,
+            indices: entries.iter().map(|&(_, j, _)| j).collect(),
+            values: entries.iter().map(|&(_, _, v)| v).collect(),
+        })
+    }
+
+    pub fn write_matrix_market<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        write_matrix_market(
+            out,
+            self.rows,
+            self.cols,
+            self.nnz(),
+            self.entries.iter().copied(),
+        )
+    }
+
+    pub fn to_matrix_market(&self) -> String {
+        let mut out: Vec<u8> = Vec::new();
+        self.write_matrix_market(&mut out)
+            .expect("writing to a Vec does not fail");
+        String::from_utf8(out).expect("Matrix Market output is ASCII")
+    }
+}
+
+impl Csr {
+    pub fn nnz(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Columns and values of the entries of a row
+    pub fn row(&self, i: usize) -> impl Iterator<Item = (usize, f64)> + '_ {
+        let range = self.indptr[i]..self.indptr[i + 1];
+        self.indices[range.clone()]
+            .iter()
+            .copied()
+            .zip(self.values[range].iter().copied())
+    }
+
+    pub fn to_coo(&self) -> Coo {
+        Coo {
+            rows: self.rows,
+            cols: self.cols,
+            entries: (0..self.rows)
+                .flat_map(|i| self.row(i).map(move |(j, v)| (i, j, v)))
+                .collect(),
+        }
+    }
+
+    pub fn write_matrix_market<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let entries = (0..self.rows).flat_map(|i| self.row(i).map(move |(j, v)| (i, j, v)));
+        write_matrix_market(out, self.rows, self.cols, self.nnz(), entries)
+    }
+
+    pub fn to_matrix_market(&self) -> String {
+        let mut out: Vec<u8> = Vec::new();
+        self.write_matrix_market(&mut out)
+            .expect("writing to a Vec does not fail");
+        String::from_utf8(out).expect("Matrix Market output is ASCII")
+    }
+}
+
+/// Square matrix over the nodes of a graph from per-node rows
+fn square<const K: usize>(
+    nodes: &[Node<K>],
+    row: impl Fn(Node<K>, &HashMap<Node<K>, usize>) -> HashMap<usize, f64>,
+) -> Coo {
+    let ids: HashMap<Node<K>, usize> = nodes.iter().enumerate().map(|(i, &n)| (n, i)).collect();
+    let mut entries: Vec<(usize, usize, f64)> = Vec::new();
+    for (i, &node) in nodes.iter().enumerate() {
+        let mut cols: Vec<(usize, f64)> = row(node, &ids)
+            .into_iter()
+            .filter(|&(_, v)| v != 0.0)
+            .collect();
+        cols.sort_unstable_by_key(|&(j, _)| j);
+        entries.extend(cols.into_iter().map(|(j, v)| (i, j, v)));
+    }
+    Coo {
+        rows: nodes.len(),
+        cols: nodes.len(),
+        entries,
+    }
+}
+
+/// Adjacency matrix with the multiplicity of the edge from node `i` to node
+/// `j` at `(i, j)`. Rows and columns follow the order of `graph.nodes()`,
+/// which is returned alongside.
+pub fn adjacency<G: Debruijn<K>, const K: usize>(graph: &G) -> (Vec<Node<K>>, Coo) {
+    let nodes = graph.nodes();
+    let matrix = square(&nodes, |node, ids| {
+        graph
+            .out_edges(node)
+            .map(|(kmer, m)| (ids[&Node::suffix(kmer)], m as f64))
+            .collect()
+    });
+    (nodes, matrix)
+}
+
+/// Directed out-degree Laplacian `D_out - A`, weighted by multiplicity, in
+/// the node order of `adjacency`. Its rows sum to zero.
+pub fn laplacian<G: Debruijn<K>, const K: usize>(graph: &G) -> (Vec<Node<K>>, Coo) {
+    let nodes = graph.nodes();
+    let matrix = square(&nodes, |node, ids| {
+        let mut row: HashMap<usize, f64> = HashMap::new();
+        for (kmer, m) in graph.out_edges(node) {
+            *row.entry(ids[&node]).or_default() += m as f64;
+            *row.entry(ids[&Node::suffix(kmer)]).or_default() -= m as f64;
+        }
+        row
+    });
+    (nodes, matrix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KmerTable;
+    use bio_seq::prelude::*;
+
+    fn graph() -> KmerTable<u32, 4> {
+        let mut graph: KmerTable<u32, 4> = KmerTable::default();
+        graph.add_seq(dna!("ACGTTACGTA"));
+        graph
+    }
+
+    #[test]
+    fn test_adjacency() {
+        let graph = graph();
+        let (nodes, adjacency) = graph.adjacency();
+        let id = |seq: &SeqSlice<Dna>| {
+            let node = Node::try_from(seq).unwrap();
+            nodes.iter().position(|&n| n == node).unwrap()
+        };
+
+        assert_eq!((adjacency.rows, adjacency.cols), (6, 6));
+        assert_eq!(adjacency.nnz(), 6);
+        assert!(
+            adjacency
+                .entries
+                .contains(&(id(dna!("ACG")), id(dna!("CGT")), 2.0))
+        );
+        assert!(adjacency.entries.is_sorted_by_key(|&(i, j, _)| (i, j)));
+
+        let csr = adjacency.to_csr().unwrap();
+        assert_eq!(csr.indptr.len(), 7);
+        let row: Vec<(usize, f64)> = csr.row(id(dna!("CGT"))).collect();
+        let mut expected = vec![(id(dna!("GTA")), 1.0), (id(dna!("GTT")), 1.0)];
+        expected.sort_unstable_by_key(|&(j, _)| j);
+        assert_eq!(row, expected);
+        assert_eq!(csr.to_coo(), adjacency);
+        assert_eq!(csr.to_matrix_market(), adjacency.to_matrix_market());
+    }
+
+    #[test]
+    fn test_laplacian() {
+        let graph = graph();
+        let (nodes, laplacian) = graph.laplacian();
+        let csr = laplacian.to_csr().unwrap();
+
+        for i in 0..nodes.len() {
+            assert_eq!(csr.row(i).map(|(_, v)| v).sum::<f64>(), 0.0);
+        }
+        let cgt = nodes
+            .iter()
+            .position(|&n| n == Node::try_from(dna!("CGT")).unwrap())
+            .unwrap();
+        assert!(csr.row(cgt).any(|entry| entry == (cgt, 2.0)));
+        // GTA has no out-edges, so its row is empty
+        assert_eq!(laplacian.nnz(), 6 + 5);
+
+        let mm = laplacian.to_matrix_market();
+        let mut lines = mm.lines();
+        assert_eq!(
+            lines.next(),
+            Some("%%MatrixMarket matrix coordinate real general")
+        );
+        assert_eq!(lines.next(), Some("6 6 11"));
+        let (i, j, v) = laplacian.entries[0];
+        assert_eq!(
+            lines.next(),
+            Some(format!("{} {} {v}", i + 1, j + 1).as_str())
+        );
+        assert_eq!(lines.count(), 10);
+    }
+
+    #[test]
+    fn test_unsorted_coo() {
+        let coo = Coo {
+            rows: 2,
+            cols: 3,
+            entries: vec![(1, 2, 1.0), (0, 1, 2.0), (1, 0, 3.0), (1, 2, 0.5)],
+        };
+        let csr = coo.to_csr().unwrap();
+        assert_eq!(csr.indptr, vec![0, 1, 3]);
+        assert_eq!(csr.row(1).collect::<Vec<_>>(), vec![(0, 3.0), (2, 1.5)]);
+        assert_eq!(
+            csr.to_matrix_market(),
+            "%%MatrixMarket matrix coordinate real general\n2 3 3\n1 2 2\n2 1 3\n2 3 1.5\n"
+        );
+
+        let outside = Coo {
+            rows: 2,
+            cols: 3,
+            entries: vec![(0, 3, 1.0)],
+        };
+        assert_eq!(
+            outside.to_csr(),
+            Err(Error::OutOfBounds { index: 3, bound: 3 })
+        );
+    }
+}
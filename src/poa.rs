@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use bio_seq::prelude::*;
+
+use crate::debruijn::BASES;
+use crate::error::{Error, Result};
+use crate::{Coloured, Dag, Debruijn, Node};
+
+/// A partial order alignment read off a DAG of a coloured de Bruijn graph.
+/// Every node after the start adds one base. The alignment is the K-1 bases
+/// of the start followed by a column per step along the longest path to
+/// the end. Nodes that reach the end are placed as late as they can go, so
+/// the branches of a bubble share columns and a shorter branch is padded
+/// with gaps where the branches split, keeping the bases it shares with
+/// the node it merges into aligned.
+#[derive(Clone, Debug)]
+pub struct Poa<const K: usize> {
+    /// Nodes in topological order, starting with the start of the DAG
+    pub nodes: Vec<Node<K>>,
+    /// Alignment column of each node: the longest distance to the end
+    /// counted back from the column of the end, or the longest distance
+    /// from the start for dead ends
+    pub columns: Vec<usize>,
+    /// Edges and their multiplicities
+    pub weights: HashMap<Kmer<Dna, K>, usize>,
+    pub samples: Vec<String>,
+    /// Heaviest path of each colour that reaches the end, as indices into
+    /// `nodes`
+    pub paths: Vec<(usize, Vec<usize>)>,
+    end: usize,
+}
+
+fn no_path<const K: usize>(start: Node<K>, end: Node<K>) -> Error {
+    Error::NoPath(format!("{end} is not reachable from {start}"))
+}
+
+impl<const K: usize> Poa<K> {
+    pub fn new(graph: &Coloured<K>, dag: &Dag<K>) -> Result<Self> {
+        let nodes = dag.toposort();
+        let end = nodes
+            .iter()
+            .position(|&node| node == dag.end)
+            .ok_or_else(|| no_path(dag.start, dag.end))?;
+
+        let index: HashMap<Node<K>, usize> =
+            nodes.iter().enumerate().map(|(i, &n)| (n, i)).collect();
+        let mut columns = vec![0; nodes.len()];
+        for (i, node) in nodes.iter().enumerate() {
+            for succ in dag.successors(*node) {
+                let j = index[&succ];
+                columns[j] = columns[j].max(columns[i] + 1);
+            }
+        }
+        // right-justify the nodes that reach the end against their
+        // successors, so shorter branches line up with where they merge
+        let mut latest: Vec<Option<usize>> = vec![None; nodes.len()];
+        latest[end] = Some(columns[end]);
+        for i in (0..nodes.len()).rev().filter(|&i| i != end) {
+            latest[i] = dag
+                .successors(nodes[i])
+                .filter_map(|succ| latest[index[&succ]])
+                .min()
+                .and_then(|column| column.checked_sub(1));
+        }
+        for (column, latest) in columns.iter_mut().zip(latest) {
+            if let Some(latest) = latest {
+                *column = latest;
+            }
+        }
+
+        let mut poa = Poa {
+            nodes,
+            columns,
+            weights: dag.edges.clone(),
+            samples: graph.samples.clone(),
+            paths: Vec::new(),
+            end,
+        };
+
+        for colour in 0..graph.samples.len() {
+            let path = poa.heaviest_path(|kmer| graph.has_colour(kmer, colour));
+            if let Some(path) = path {
+                poa.paths.push((colour, path));
+            }
+        }
+        Ok(poa)
+    }
+
+    /// Path from the start to the end with the greatest total edge weight
+    /// using only edges that pass a filter
+    fn heaviest_path(&self, filter: impl Fn(Kmer<Dna, K>) -> bool) -> Option<Vec<usize>> {
+        let index: HashMap<Node<K>, usize> = self
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(i, &n)| (n, i))
+            .collect();
+        // best score reaching each node and the node it was reached from
+        let mut best: Vec<Option<(usize, usize)>> = vec![None; self.nodes.len()];
+        best[0] = Some((0, 0));
+
+        for (i, node) in self.nodes.iter().enumerate() {
+            let Some((score, _)) = best[i] else {
+                continue;
+            };
+            for base in BASES {
+                let kmer = node.extend(base);
+                let Some(&weight) = self.weights.get(&kmer) else {
+                    continue;
+                };
+                if !filter(kmer) {
+                    continue;
+                }
+                let j = index[&Node::suffix(kmer)];
+                if best[j].is_none_or(|(s, _)| score + weight > s) {
+                    best[j] = Some((score + weight, i));
+                }
+            }
+        }
+
+        best[self.end]?;
+        let mut path = vec![self.end];
+        let mut node = self.end;
+        while node != 0 {
+            node = best[node]?.1;
+            path.push(node);
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    /// Heaviest path over all colours
+    pub fn consensus(&self) -> Result<Seq<Dna>> {
+        let path = self
+            .heaviest_path(|_| true)
+            .ok_or_else(|| no_path(self.nodes[0], self.nodes[self.end]))?;
+        Ok(self.spell(&path))
+    }
+
+    /// Sequence of a path of node indices
+    pub fn spell(&self, path: &[usize]) -> Seq<Dna> {
+        let mut seq: Seq<Dna> = self.nodes[0].to_seq();
+        seq.extend(path.iter().skip(1).map(|&i| self.nodes[i].last()));
+        seq
+    }
+
+    /// Write the colour paths as gapped FASTA, leaving out the columns
+    /// that no path visits
+    pub fn write_fasta<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let width = self.columns.iter().max().map_or(0, |&c| c + 1);
+        let mut used = vec![false; width];
+        for (_, path) in &self.paths {
+            for &i in path {
+                used[self.columns[i]] = true;
+            }
+        }
+
+        for (colour, path) in &self.paths {
+            let mut row: Vec<Option<Dna>> = vec![None; width];
+            for &i in &path[1..] {
+                row[self.columns[i]] = Some(self.nodes[i].last());
+            }
+            writeln!(out, ">{}", self.samples[*colour])?;
+            write!(out, "{}", self.nodes[0])?;
+            for column in (1..width).filter(|&c| used[c]) {
+                match row[column] {
+                    Some(base) => write!(out, "{}", base.to_char())?,
+                    None => write!(out, "-")?,
+                }
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+
+    pub fn to_fasta(&self) -> String {
+        let mut out: Vec<u8> = Vec::new();
+        self.write_fasta(&mut out)
+            .expect("writing to a Vec does not fail");
+        String::from_utf8(out).expect("FASTA output is ASCII")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_poa() {
+        let mut graph: Coloured<4> = Coloured::default();
+        graph.add_sample("x", dna!("GGACTTTGCC"));
+        graph.add_sample("y", dna!("GGACAATGCC"));
+        graph.add_sample("z", dna!("GGACAATGCC"));
+        // a colour that never reaches the end has no row
+        graph.add_sample("w", dna!("GGACTTAA"));
+
+        let start = Node::try_from(dna!("GGA")).unwrap();
+        let end = Node::try_from(dna!("GCC")).unwrap();
+        let poa = Poa::new(&graph, &graph.subdawg(start, end).unwrap()).unwrap();
+
+        assert_eq!(poa.paths.len(), 3);
+        assert_eq!(poa.spell(&poa.paths[0].1), dna!("GGACTTTGCC"));
+        assert_eq!(poa.consensus().unwrap(), dna!("GGACAATGCC"));
+
+        // the alleles TTTG and AATG are the same length and share columns
+        let column = |seq: &SeqSlice<Dna>| {
+            let node = Node::try_from(seq).unwrap();
+            poa.columns[poa.nodes.iter().position(|&n| n == node).unwrap()]
+        };
+        assert_eq!(column(dna!("ACT")), column(dna!("ACA")));
+        assert_eq!(column(dna!("TTG")), column(dna!("ATG")));
+        assert_eq!(
+            poa.to_fasta(),
+            ">x\nGGACTTTGCC\n>y\nGGACAATGCC\n>z\nGGACAATGCC\n"
+        );
+    }
+
+    #[test]
+    fn test_poa_indel() {
+        let mut graph: Coloured<4> = Coloured::default();
+        graph.add_sample("x", dna!("GGACTTTGCC"));
+        graph.add_sample("y", dna!("GGACATGCC"));
+
+        let start = Node::try_from(dna!("GGA")).unwrap();
+        let end = Node::try_from(dna!("GCC")).unwrap();
+        let mut dag = graph.subdawg(start, end).unwrap();
+        let poa = Poa::new(&graph, &dag).unwrap();
+        // only the length difference is padded, before the bases the
+        // shorter branch shares with the merge
+        assert_eq!(poa.to_fasta(), ">x\nGGACTTTGCC\n>y\nGGAC-ATGCC\n");
+
+        dag.end = Node::try_from(dna!("AAA")).unwrap();
+        assert!(matches!(Poa::new(&graph, &dag), Err(Error::NoPath(_))));
+    }
+}
@@ -0,0 +1,337 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::ops::RangeInclusive;
+
+use bio_seq::prelude::*;
+
+use crate::error::{Error, Result};
+
+/// Longest k that fits a 2-bit packed `u64`
+pub const MAX_K: usize = 32;
+
+/// Statistics of the k-mer spectrum of a collection of sequences for one k
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct KStats {
+    pub k: usize,
+    /// Number of k-mer positions
+    pub total: usize,
+    pub distinct: usize,
+    /// Shannon entropy of the k-mer frequencies in base 4
+    pub entropy: f64,
+    /// `4^entropy`, the effective number of distinct k-mers
+    pub perplexity: f64,
+    /// Fraction of the k-mer positions that are distinct k-mers
+    pub distinct_fraction: f64,
+}
+
+/// Spectrum statistics over a range of k with a suggested k
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scan {
+    pub stats: Vec<KStats>,
+    /// The smallest k whose perplexity per k-mer position is within 1% of
+    /// the best in the range. Perplexity reaches the number of positions
+    /// once every k-mer is unique, so longer k-mers would not resolve more
+    /// repeats.
+    pub recommended: Option<usize>,
+}
+
+/// Shannon entropy in base 4 of a distribution given by counts
+pub fn entropy(counts: impl IntoIterator<Item = usize>) -> f64 {
+    let counts: Vec<usize> = counts.into_iter().filter(|&c| c > 0).collect();
+    let total: usize = counts.iter().sum();
+    if total == 0 {
+        return 0.0;
+    }
+    let total = total as f64;
+    -counts
+        .iter()
+        .map(|&c| {
+            let p = c as f64 / total;
+            p * p.log(4.0)
+        })
+        .sum::<f64>()
+}
+
+/// Count the k-mers of each sequence with k chosen at runtime, packed two
+/// bits per base into a `u64`
+pub fn kmer_counts(seqs: &[&SeqSlice<Dna>], k: usize) -> Result<HashMap<u64, usize>> {
+    if k == 0 || k > MAX_K {
+        return Err(Error::OutOfBounds {
+            index: k,
+            bound: MAX_K + 1,
+        });
+    }
+    let mask: u64 = if k == MAX_K {
+        u64::MAX
+    } else {
+        (1 << (k * Dna::BITS as usize)) - 1
+    };
+
+    let mut counts: HashMap<u64, usize> = HashMap::new();
+    for seq in seqs {
+        let mut code: u64 = 0;
+        for (i, base) in seq.iter().enumerate() {
+            code = ((code << Dna::BITS) | base as u64) & mask;
+            if i + 1 >= k {
+                *counts.entry(code).or_default() += 1;
+            }
+        }
+    }
+    Ok(counts)
+}
+
+pub fn stats(seqs: &[&SeqSlice<Dna>], k: usize) -> Result<KStats> {
+    let counts = kmer_counts(seqs, k)?;
+    let total: usize = counts.values().sum();
+    let entropy = entropy(counts.values().copied());
+
+    Ok(KStats {
+        k,
+        total,
+        distinct: counts.len(),
+        entropy,
+        perplexity: 4f64.powf(entropy),
+        distinct_fraction: if total > 0 {
+            counts.len() as f64 / total as f64
+        } else {
+            0.0
+        },
+    })
+}
+
+/// Spectrum statistics for every k in a range
+pub fn scan(seqs: &[&SeqSlice<Dna>], ks: RangeInclusive<usize>) -> Result<Scan> {
+    let stats: Vec<KStats> = ks.map(|k| stats(seqs, k)).collect::<Result<_>>()?;
+
+    let uniqueness = |s: &KStats| {
+        if s.total > 0 {
+            s.perplexity / s.total as f64
+        } else {
+            0.0
+        }
+    };
+    let best = stats.iter().map(uniqueness).fold(0.0, f64::max);
+    let recommended = stats
+        .iter()
+        .find(|s| s.total > 0 && uniqueness(s) >= 0.99 * best)
+        .map(|s| s.k);
+
+    Ok(Scan { stats, recommended })
+}
+
+/// Counts of two spectra side by side over the union of their k-mers
+fn paired<T: Eq + Hash>(
+    p: impl IntoIterator<Item = (T, usize)>,
+    q: impl IntoIterator<Item = (T, usize)>,
+) -> Vec<(f64, f64)> {
+    let mut counts: HashMap<T, (usize, usize)> = HashMap::new();
+    for (kmer, c) in p {
+        counts.entry(kmer).or_default().0 += c;
+    }
+    for (kmer, c) in q {
+        counts.entry(kmer).or_default().1 += c;
+    }
+    counts
+        .into_values()
+        .filter(|&(a, b)| a > 0 || b > 0)
+        .map(|(a, b)| (a as f64, b as f64))
+        .collect()
+}
+
+/// Add `pseudocount` to every count and normalise each side to sum to 1
+fn smooth(counts: &mut [(f64, f64)], pseudocount: f64) {
+    let (total_p, total_q) = counts
+        .iter()
+        .fold((0.0, 0.0), |(tp, tq), &(a, b)| (tp + a, tq + b));
+    let n = counts.len() as f64;
+    let (total_p, total_q) = (total_p + pseudocount * n, total_q + pseudocount * n);
+    for (a, b) in counts.iter_mut() {
+        *a = (*a + pseudocount) / total_p;
+        *b = (*b + pseudocount) / total_q;
+    }
+}
+
+fn kl(pairs: impl Iterator<Item = (f64, f64)>) -> f64 {
+    pairs
+        .filter(|&(p, _)| p > 0.0)
+        .map(|(p, q)| p * (p / q).log2())
+        .sum()
+}
+
+/// Kullback–Leibler divergence `D(p || q)` in bits between two k-mer
+/// spectra given as `(kmer, count)` pairs. `pseudocount` is added to the
+/// count of every k-mer seen in either spectrum before normalising. Without
+/// it the divergence is infinite if `q` lacks a k-mer of `p`. An empty
+/// spectrum has no distribution to compare, so the divergence is NaN if
+/// either side is empty after smoothing.
+pub fn kl_divergence<T: Eq + Hash>(
+    p: impl IntoIterator<Item = (T, usize)>,
+    q: impl IntoIterator<Item = (T, usize)>,
+    pseudocount: f64,
+) -> f64 {
+    let mut counts = paired(p, q);
+    if empty(&counts, pseudocount) {
+        return f64::NAN;
+    }
+    smooth(&mut counts, pseudocount);
+    kl(counts.into_iter())
+}
+
+/// Jensen–Shannon divergence in bits between two k-mer spectra, smoothed as
+/// in [`kl_divergence`]. It is symmetric and between 0 and 1, or NaN if
+/// either spectrum is empty.
+pub fn js_divergence<T: Eq + Hash>(
+    p: impl IntoIterator<Item = (T, usize)>,
+    q: impl IntoIterator<Item = (T, usize)>,
+    pseudocount: f64,
+) -> f64 {
+    let mut counts = paired(p, q);
+    if empty(&counts, pseudocount) {
+        return f64::NAN;
+    }
+    smooth(&mut counts, pseudocount);
+    let mixture = |&(p, q): &(f64, f64)| (p + q) / 2.0;
+    let d_p = kl(counts.iter().map(|pair| (pair.0, mixture(pair))));
+    let d_q = kl(counts.iter().map(|pair| (pair.1, mixture(pair))));
+    // rounding can leave a tiny negative value for identical spectra
+    ((d_p + d_q) / 2.0).max(0.0)
+}
+
+/// Whether either side has no mass to normalise
+fn empty(counts: &[(f64, f64)], pseudocount: f64) -> bool {
+    if pseudocount > 0.0 {
+        return counts.is_empty();
+    }
+    counts.iter().all(|&(a, _)| a == 0.0) || counts.iter().all(|&(_, b)| b == 0.0)
+}
+
+/// Symmetric matrix of Jensen–Shannon distances, the square root of the
+/// divergence, between every pair of k-mer spectra. The distance is a
+/// metric. The distance between an empty spectrum and any other is NaN.
+pub fn distances<T: Eq + Hash + Clone>(
+    samples: &[HashMap<T, usize>],
+    pseudocount: f64,
+) -> Vec<Vec<f64>> {
+    let n = samples.len();
+    let mut matrix = vec![vec![0.0; n]; n];
+    for i in 0..n {
+        for j in i + 1..n {
+            let d = js_divergence(
+                samples[i].iter().map(|(kmer, &c)| (kmer.clone(), c)),
+                samples[j].iter().map(|(kmer, &c)| (kmer.clone(), c)),
+                pseudocount,
+            )
+            .sqrt();
+            matrix[i][j] = d;
+            matrix[j][i] = d;
+        }
+    }
+    matrix
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entropy() {
+        assert_eq!(entropy([5]), 0.0);
+        assert_eq!(entropy([1, 1, 1, 1]), 1.0);
+        assert_eq!(entropy([2, 2]), 0.5);
+        assert_eq!(entropy([]), 0.0);
+    }
+
+    #[test]
+    fn test_kmer_counts() {
+        let counts = kmer_counts(&[dna!("ACGTACG"), dna!("AC")], 3).unwrap();
+        assert_eq!(counts.values().sum::<usize>(), 5);
+        assert_eq!(counts.len(), 4);
+
+        let kmer: Kmer<Dna, 3> = Kmer::try_from(dna!("ACG")).unwrap();
+        // runtime codes are big-endian in the bases, unlike `Kmer`
+        let code = kmer.iter().fold(0, |code, base| (code << 2) | base as u64);
+        assert_eq!(counts[&code], 2);
+
+        assert!(kmer_counts(&[dna!("ACGT")], 0).is_err());
+        assert!(kmer_counts(&[dna!("ACGT")], 33).is_err());
+        assert_eq!(kmer_counts(&[dna!("ACGT")], 32).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_divergence() {
+        let p = [("A", 3), ("C", 1)];
+        let q = [("A", 1), ("C", 3)];
+
+        assert_eq!(kl_divergence(p, p, 0.0), 0.0);
+        let d = 0.75 * 3f64.log2() - 0.25 * 3f64.log2();
+        assert!((kl_divergence(p, q, 0.0) - d).abs() < 1e-12);
+
+        // a k-mer missing from q
+        let r = [("A", 4)];
+        assert_eq!(kl_divergence(p, r, 0.0), f64::INFINITY);
+        assert!(kl_divergence(r, p, 0.0).is_finite());
+        // smoothed: p = (4, 2) / 6 and r = (5, 1) / 6
+        let d = 4.0 / 6.0 * (4.0f64 / 5.0).log2() + 2.0 / 6.0 * 2f64.log2();
+        assert!((kl_divergence(p, r, 1.0) - d).abs() < 1e-12);
+
+        assert_eq!(js_divergence(p, p, 1.0), 0.0);
+        assert_eq!(js_divergence([("A", 1)], [("C", 1)], 0.0), 1.0);
+        assert!((js_divergence(p, q, 0.5) - js_divergence(q, p, 0.5)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_empty_divergence() {
+        let p = [("A", 3), ("C", 1)];
+        let empty: [(&str, usize); 0] = [];
+
+        assert!(kl_divergence(p, empty, 0.0).is_nan());
+        assert!(kl_divergence(empty, p, 0.0).is_nan());
+        assert!(js_divergence(p, empty, 0.0).is_nan());
+        assert!(js_divergence(empty, empty, 1.0).is_nan());
+        // smoothing gives the empty side a uniform distribution
+        assert!(js_divergence(p, empty, 1.0).is_finite());
+
+        let samples = [HashMap::from([("A", 3), ("C", 1)]), HashMap::new()];
+        let matrix = distances(&samples, 0.0);
+        assert!(matrix[0][1].is_nan() && matrix[1][0].is_nan());
+    }
+
+    #[test]
+    fn test_distances() {
+        let seqs = [dna!("ACGTACGTAC"), dna!("ACGTACGTAA"), dna!("TTTTGGGGCC")];
+        let samples: Vec<HashMap<u64, usize>> = seqs
+            .iter()
+            .map(|&seq| kmer_counts(&[seq], 3).unwrap())
+            .collect();
+        let matrix = distances(&samples, 0.0);
+
+        for (i, row) in matrix.iter().enumerate() {
+            assert_eq!(row[i], 0.0);
+            for (j, &d) in row.iter().enumerate() {
+                assert_eq!(d, matrix[j][i]);
+            }
+        }
+        // no shared k-mers
+        assert_eq!(matrix[0][2], 1.0);
+        assert!(matrix[0][1] < matrix[1][2]);
+    }
+
+    #[test]
+    fn test_scan() {
+        // GATTACAGCTTGA occurs twice, so k-mers are all unique from k = 14
+        let seq = dna!("GATTACAGCTTGACCATGGATTACAGCTTGAATCGTCA");
+        let result = scan(&[seq], 2..=16).unwrap();
+
+        assert_eq!(result.stats.len(), 15);
+        let k13 = result.stats[11];
+        assert_eq!((k13.k, k13.total, k13.distinct), (13, 26, 25));
+        let k14 = result.stats[12];
+        assert_eq!((k14.total, k14.distinct), (25, 25));
+        assert!((k14.perplexity - 25.0).abs() < 1e-9);
+        assert_eq!(k14.distinct_fraction, 1.0);
+        assert_eq!(result.recommended, Some(14));
+
+        // no k-mers at all
+        assert!(scan(&[dna!("ACGT")], 5..=6).unwrap().recommended.is_none());
+    }
+}